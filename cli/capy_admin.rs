@@ -0,0 +1,1138 @@
+//! `capy-admin`: operates the `capy_solana_token` deployment.
+//!
+//! Every command builds its instructions with the typed client, signs with
+//! the authority keypair (plus any `--cosigner` keypairs when the authority
+//! is an M-of-N signer set) and reads the Wormhole addresses, chain ids and
+//! NTT peers from `config/bridge_config.json`.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::{instruction::Instruction, keccak};
+use anchor_lang::Discriminator;
+use anyhow::{anyhow, bail, Context, Result};
+use capy_solana_client::mock_wormhole::{MockGuardianSet, VaaBody};
+use capy_solana_client::{self as client, instructions, pda, AllocationWallets};
+use capy_solana_token::{
+    to_base_units, OutboundTransfer, ParameterChange, UserStakeInfo, VaaClaim, WormholeConfig,
+    DECIMALS, TRIMMED_DECIMALS,
+};
+use clap::{Parser, Subcommand};
+use serde::Deserialize;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::compute_budget::ComputeBudgetInstruction;
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use solana_sdk::signature::{read_keypair_file, Keypair, Signature, Signer};
+use solana_sdk::transaction::Transaction;
+use spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+
+const SOLANA_CHAIN: &str = "solana";
+const COMPUTE_UNIT_LIMIT: u32 = 400_000;
+
+#[derive(Parser)]
+#[command(
+    name = "capy-admin",
+    about = "Admin tool for the CAPYAI Solana program"
+)]
+struct Cli {
+    /// Bridge configuration with the Wormhole addresses
+    #[arg(long, default_value = "config/bridge_config.json")]
+    config: PathBuf,
+    /// RPC endpoint of the cluster
+    #[arg(long, short = 'u', default_value = "http://127.0.0.1:8899")]
+    url: String,
+    /// Authority keypair, which also pays for transactions
+    #[arg(long, short = 'k', default_value = "~/.config/solana/id.json")]
+    keypair: String,
+    /// Additional authority signer keypairs for M-of-N approvals
+    #[arg(long = "cosigner")]
+    cosigners: Vec<String>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Creates the mint, the transfer hook and the program config with the
+    /// default lock tiers, then exempts the program vaults from the hook
+    Initialize {
+        /// Wallet that owns the treasury token account
+        #[arg(long)]
+        treasury_owner: Pubkey,
+        /// Wallet whose token account receives development vesting
+        #[arg(long)]
+        development_owner: Pubkey,
+        /// Wallet whose token account receives marketing vesting
+        #[arg(long)]
+        marketing_owner: Pubkey,
+        /// Wallet whose token account receives team vesting
+        #[arg(long)]
+        team_owner: Pubkey,
+    },
+    /// Pauses staking and bridging
+    Pause,
+    /// Resumes staking and bridging
+    Unpause,
+    /// Updates a parameter; timelocked parameters are scheduled
+    Set {
+        #[command(subcommand)]
+        parameter: Parameter,
+    },
+    /// Applies a scheduled change once its timelock has expired
+    ExecuteChange { id: u64 },
+    /// Registers the NTT peer of a foreign chain
+    RegisterEmitter {
+        /// Chain name from the bridge config, or a Wormhole chain id
+        chain: String,
+        /// Wormhole transceiver of the peer; defaults to the bridge config
+        #[arg(long)]
+        address: Option<String>,
+        /// NTT manager of the peer; defaults to the bridge config
+        #[arg(long)]
+        manager: Option<String>,
+        /// Token decimals on the peer chain; defaults to the bridge config
+        #[arg(long)]
+        decimals: Option<u8>,
+    },
+    /// Prints the program config, staking pool and lock tiers
+    ShowConfig,
+    /// Lists stake positions
+    ListStakers {
+        /// Only list the positions of this owner
+        #[arg(long)]
+        owner: Option<Pubkey>,
+    },
+    /// Decodes a VAA and checks it against the deployment
+    InspectVaa {
+        /// VAA bytes in hex or base64
+        vaa: String,
+    },
+    /// Mints the transfer of a VAA. Does not post the VAA: the core bridge
+    /// must already have verified its signatures and posted it
+    RedeemVaa {
+        /// VAA bytes in hex or base64
+        vaa: String,
+    },
+    /// Prints the limits and remaining capacity of every peer
+    ShowRateLimits,
+    /// Lists inbound transfers queued by the rate limit
+    ListQueued,
+    /// Mints a queued inbound transfer; before its release time only the
+    /// authority can
+    ReleaseInbound {
+        /// VAA bytes in hex or base64
+        vaa: String,
+    },
+    /// Re-mints an outbound transfer the destination chain rejected
+    RefundTransfer {
+        /// `TransferRejected` VAA bytes in hex or base64
+        vaa: String,
+        /// Keypair of the original sender; defaults to --keypair
+        #[arg(long)]
+        sender: Option<String>,
+    },
+    /// Signs a payload with the deterministic mock guardian set, for dry
+    /// runs against a core bridge whose guardian set is the printed addresses
+    SignVaa {
+        /// Payload bytes in hex or base64
+        payload: String,
+        /// Chain the payload is emitted from, e.g. ethereum
+        #[arg(long)]
+        chain: String,
+        /// Emitter address; defaults to the chain's NTT transceiver
+        #[arg(long)]
+        emitter: Option<String>,
+        #[arg(long, default_value_t = 0)]
+        sequence: u64,
+        /// Number of mock guardians
+        #[arg(long, default_value_t = 19)]
+        guardians: u8,
+        #[arg(long, default_value_t = 0)]
+        guardian_set_index: u32,
+    },
+}
+
+#[derive(Subcommand)]
+enum Parameter {
+    /// Minimum stake, in whole tokens
+    MinStakeAmount { amount: u64 },
+    /// Token account that receives penalties and withheld fees
+    TreasuryWallet { wallet: Pubkey },
+    /// Core bridge and message fee from the bridge config
+    WormholeConfig,
+    /// Transfer fee in percent (timelocked)
+    TransferTaxRate { rate: u64 },
+    /// Reward emission in base units per second (timelocked)
+    EmissionRate { rate: u64 },
+    /// Wormhole consistency level (timelocked)
+    ConsistencyLevel { level: u8 },
+    /// Bridge fee in basis points, paid to the treasury (timelocked)
+    BridgeFee { bps: u16 },
+    /// Per-window bridge limits of a peer chain, in whole tokens
+    RateLimits {
+        chain: String,
+        outbound: u64,
+        inbound: u64,
+    },
+    /// Exempts a wallet from the transfer limit
+    Exemption {
+        owner: Pubkey,
+        /// Removes the exemption instead
+        #[arg(long)]
+        revoke: bool,
+    },
+    /// Largest transfer of non-exempt wallets, in whole tokens
+    MaxTransferAmount { amount: u64 },
+}
+
+#[derive(Deserialize)]
+struct BridgeConfig {
+    wormhole: WormholeSettings,
+    tokens: HashMap<String, TokenSettings>,
+}
+
+#[derive(Deserialize)]
+struct WormholeSettings {
+    core_bridge: HashMap<String, String>,
+    chain_ids: HashMap<String, u16>,
+    ntt: NttSettings,
+    consistency_level: u8,
+    fee: f64,
+}
+
+#[derive(Deserialize)]
+struct NttSettings {
+    managers: HashMap<String, String>,
+    transceivers: HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct TokenSettings {
+    decimals: u8,
+}
+
+impl BridgeConfig {
+    fn load(path: &Path) -> Result<Self> {
+        let file =
+            std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        serde_json::from_str(&file).with_context(|| format!("parsing {}", path.display()))
+    }
+
+    fn core_bridge(&self) -> Result<Pubkey> {
+        let address = self
+            .wormhole
+            .core_bridge
+            .get(SOLANA_CHAIN)
+            .ok_or_else(|| anyhow!("no Solana core bridge in the bridge config"))?;
+        address
+            .parse()
+            .with_context(|| format!("invalid core bridge address {address}"))
+    }
+
+    fn wormhole_config(&self) -> Result<WormholeConfig> {
+        Ok(WormholeConfig {
+            bridge: pda::wormhole_bridge(&self.core_bridge()?),
+            message_fee: (self.wormhole.fee * LAMPORTS_PER_SOL as f64).round() as u64,
+            consistency_level: self.wormhole.consistency_level,
+        })
+    }
+
+    /// Resolves a chain name or a numeric Wormhole chain id.
+    fn chain_id(&self, chain: &str) -> Result<u16> {
+        if let Ok(id) = chain.parse() {
+            return Ok(id);
+        }
+        self.wormhole
+            .chain_ids
+            .get(chain)
+            .copied()
+            .ok_or_else(|| anyhow!("unknown chain {chain}"))
+    }
+
+    fn chain_name(&self, id: u16) -> String {
+        self.wormhole
+            .chain_ids
+            .iter()
+            .find(|(_, chain_id)| **chain_id == id)
+            .map_or_else(|| format!("chain {id}"), |(name, _)| name.clone())
+    }
+
+    fn ntt_address<'a>(
+        addresses: &'a HashMap<String, String>,
+        chain: &str,
+        what: &str,
+    ) -> Result<&'a str> {
+        match addresses.get(chain) {
+            Some(address) if !address.is_empty() => Ok(address),
+            _ => bail!("no NTT {what} for {chain} in the bridge config"),
+        }
+    }
+
+    fn token_decimals(&self, chain: &str) -> Result<u8> {
+        self.tokens
+            .get(chain)
+            .map(|token| token.decimals)
+            .ok_or_else(|| anyhow!("no token decimals for {chain} in the bridge config"))
+    }
+}
+
+struct Admin {
+    rpc: RpcClient,
+    bridge_config: BridgeConfig,
+    authority: Keypair,
+    cosigners: Vec<Keypair>,
+}
+
+impl Admin {
+    fn authority(&self) -> Pubkey {
+        self.authority.pubkey()
+    }
+
+    fn cosigners(&self) -> Vec<Pubkey> {
+        self.cosigners.iter().map(Signer::pubkey).collect()
+    }
+
+    fn send(&self, instructions: &[Instruction], signers: &[&Keypair]) -> Result<Signature> {
+        let mut all_instructions = vec![ComputeBudgetInstruction::set_compute_unit_limit(
+            COMPUTE_UNIT_LIMIT,
+        )];
+        all_instructions.extend_from_slice(instructions);
+
+        let mut all_signers = vec![&self.authority];
+        all_signers.extend(&self.cosigners);
+        all_signers.extend_from_slice(signers);
+
+        let blockhash = self.rpc.get_latest_blockhash()?;
+        let transaction = Transaction::new_signed_with_payer(
+            &all_instructions,
+            Some(&self.authority()),
+            &all_signers,
+            blockhash,
+        );
+        let signature = self.rpc.send_and_confirm_transaction(&transaction)?;
+        println!("{signature}");
+        Ok(signature)
+    }
+
+    fn account_data(&self, address: &Pubkey) -> Result<Option<Vec<u8>>> {
+        Ok(self
+            .rpc
+            .get_account_with_commitment(address, self.rpc.commitment())?
+            .value
+            .map(|account| account.data))
+    }
+
+    fn fetch<T>(&self, address: &Pubkey, decode: fn(&[u8]) -> anchor_lang::Result<T>) -> Result<T> {
+        let data = self
+            .account_data(address)?
+            .ok_or_else(|| anyhow!("account {address} does not exist"))?;
+        Ok(decode(&data)?)
+    }
+
+    /// `vesting_owners` are the development, marketing and team wallets, in
+    /// that order; their token accounts must exist before `initialize`.
+    fn initialize(&self, treasury_owner: Pubkey, vesting_owners: [Pubkey; 3]) -> Result<()> {
+        let authority = self.authority();
+        let mint = pda::mint();
+
+        // Also initializes the transfer hook with the config PDA as its authority
+        self.send(&[instructions::initialize_mint(&authority)], &[])?;
+
+        let create_wallets: Vec<_> = vesting_owners
+            .iter()
+            .map(|owner| {
+                create_associated_token_account_idempotent(
+                    &authority,
+                    owner,
+                    &mint,
+                    &anchor_spl::token_2022::ID,
+                )
+            })
+            .collect();
+        self.send(&create_wallets, &[])?;
+        let [development_owner, marketing_owner, team_owner] = vesting_owners;
+        let wallets = AllocationWallets {
+            treasury_owner,
+            development_wallet: pda::token_account(&development_owner),
+            marketing_wallet: pda::token_account(&marketing_owner),
+            team_wallet: pda::token_account(&team_owner),
+        };
+
+        let wormhole_config = self.bridge_config.wormhole_config()?;
+        self.send(
+            &[instructions::initialize(
+                &authority,
+                &wallets,
+                wormhole_config,
+            )],
+            &[],
+        )?;
+
+        println!("mint: {mint}");
+        println!("config: {}", pda::config());
+        Ok(())
+    }
+
+    fn set(&self, parameter: Parameter) -> Result<()> {
+        let authority = self.authority();
+        let cosigners = self.cosigners();
+        let change = match parameter {
+            Parameter::MinStakeAmount { amount } => {
+                let instruction = instructions::set_min_stake_amount(
+                    &authority,
+                    &cosigners,
+                    to_base_units(amount),
+                );
+                self.send(&[instruction], &[])?;
+                return Ok(());
+            }
+            Parameter::TreasuryWallet { wallet } => {
+                let instruction =
+                    instructions::set_treasury_wallet(&authority, &cosigners, &wallet);
+                self.send(&[instruction], &[])?;
+                return Ok(());
+            }
+            Parameter::WormholeConfig => {
+                // The consistency level only changes through the timelock,
+                // so keep the one on chain
+                let config = self.fetch(&pda::config(), client::decode_config)?;
+                let wormhole_config = WormholeConfig {
+                    consistency_level: config.wormhole_config.consistency_level,
+                    ..self.bridge_config.wormhole_config()?
+                };
+                let instruction =
+                    instructions::set_wormhole_config(&authority, &cosigners, wormhole_config);
+                self.send(&[instruction], &[])?;
+                return Ok(());
+            }
+            Parameter::RateLimits {
+                chain,
+                outbound,
+                inbound,
+            } => {
+                let instruction = instructions::set_rate_limits(
+                    &authority,
+                    &cosigners,
+                    self.bridge_config.chain_id(&chain)?,
+                    to_base_units(outbound),
+                    to_base_units(inbound),
+                );
+                self.send(&[instruction], &[])?;
+                return Ok(());
+            }
+            Parameter::Exemption { owner, revoke } => {
+                let instruction =
+                    instructions::set_exemption(&authority, &cosigners, &owner, !revoke);
+                self.send(&[instruction], &[])?;
+                return Ok(());
+            }
+            Parameter::MaxTransferAmount { amount } => {
+                let instruction = instructions::set_max_transfer_amount(
+                    &authority,
+                    &cosigners,
+                    to_base_units(amount),
+                );
+                self.send(&[instruction], &[])?;
+                return Ok(());
+            }
+            Parameter::TransferTaxRate { rate } => ParameterChange::TransferTaxRate(rate),
+            Parameter::EmissionRate { rate } => ParameterChange::EmissionRate(rate),
+            Parameter::ConsistencyLevel { level } => ParameterChange::ConsistencyLevel(level),
+            Parameter::BridgeFee { bps } => ParameterChange::BridgeFee(bps),
+        };
+
+        let config = self.fetch(&pda::config(), client::decode_config)?;
+        let id = config.next_change_id;
+        self.send(
+            &[instructions::schedule_change(
+                &authority, &cosigners, id, change,
+            )],
+            &[],
+        )?;
+        let pending_change = self.fetch(&pda::pending_change(id), client::decode_pending_change)?;
+        println!(
+            "scheduled change {id}; run `capy-admin execute-change {id}` after {}",
+            pending_change.eta
+        );
+        Ok(())
+    }
+
+    fn execute_change(&self, id: u64) -> Result<()> {
+        let pending_change = self.fetch(&pda::pending_change(id), client::decode_pending_change)?;
+        self.send(
+            &[instructions::execute_change(
+                id,
+                &pending_change.scheduled_by,
+            )],
+            &[],
+        )?;
+        Ok(())
+    }
+
+    fn register_emitter(
+        &self,
+        chain: &str,
+        address: Option<String>,
+        manager: Option<String>,
+        decimals: Option<u8>,
+    ) -> Result<()> {
+        let chain_id = self.bridge_config.chain_id(chain)?;
+        let ntt = &self.bridge_config.wormhole.ntt;
+        let address = match address {
+            Some(address) => parse_emitter_address(&address)?,
+            None => parse_emitter_address(BridgeConfig::ntt_address(
+                &ntt.transceivers,
+                chain,
+                "transceiver",
+            )?)?,
+        };
+        let manager = match manager {
+            Some(manager) => parse_emitter_address(&manager)?,
+            None => {
+                parse_emitter_address(BridgeConfig::ntt_address(&ntt.managers, chain, "manager")?)?
+            }
+        };
+        let decimals = match decimals {
+            Some(decimals) => decimals,
+            None => self.bridge_config.token_decimals(chain)?,
+        };
+
+        self.send(
+            &[instructions::register_emitter(
+                &self.authority(),
+                &self.cosigners(),
+                chain_id,
+                address,
+                manager,
+                decimals,
+            )],
+            &[],
+        )?;
+        println!(
+            "registered {} transceiver 0x{} with manager 0x{} ({decimals} decimals)",
+            self.bridge_config.chain_name(chain_id),
+            hex::encode(address),
+            hex::encode(manager)
+        );
+        Ok(())
+    }
+
+    fn show_config(&self) -> Result<()> {
+        let config = self.fetch(&pda::config(), client::decode_config)?;
+        println!("config: {}", pda::config());
+        println!("mint: {}", config.mint);
+        println!("authority: {}", config.authority);
+        if config.pending_authority != Pubkey::default() {
+            println!("pending authority: {}", config.pending_authority);
+        }
+        if config.is_multisig() {
+            println!(
+                "authority signers ({} of {}):",
+                config.authority_threshold,
+                config.authority_signers.len()
+            );
+            for signer in &config.authority_signers {
+                println!("  {signer}");
+            }
+        }
+        println!("paused: {}", config.paused);
+        println!("treasury wallet: {}", config.treasury_wallet);
+        println!("development wallet: {}", config.development_wallet);
+        println!("marketing wallet: {}", config.marketing_wallet);
+        println!("team wallet: {}", config.team_wallet);
+        let mint = self.fetch(&config.mint, client::decode_mint)?;
+        println!("total supply: {}", format_tokens(mint.supply));
+        println!(
+            "transfer tax rate: {}% from epoch {}",
+            config.transfer_tax_rate, config.transfer_tax_rate_epoch
+        );
+        println!("bridge fee: {} bps", config.bridge_fee_bps);
+        println!(
+            "min stake amount: {}",
+            format_tokens(config.min_stake_amount)
+        );
+        println!("wormhole bridge: {}", config.wormhole_config.bridge);
+        println!(
+            "wormhole max message fee: {} lamports",
+            config.wormhole_config.message_fee
+        );
+        println!(
+            "wormhole consistency level: {}",
+            config.wormhole_config.consistency_level
+        );
+        println!("next change id: {}", config.next_change_id);
+
+        let pool = self.fetch(&pda::staking_pool(), client::decode_staking_pool)?;
+        println!(
+            "emission rate: {} per second",
+            format_tokens(pool.emission_rate)
+        );
+        println!(
+            "emitted: {} of {}",
+            format_tokens(pool.emitted),
+            format_tokens(pool.emission_cap)
+        );
+        println!("total stake weight: {}", pool.total_weight);
+
+        if let Some(data) = self.account_data(&pda::lock_tiers())? {
+            let lock_tiers = client::decode_lock_tiers(&data)?;
+            println!(
+                "early exit penalty: {} bps",
+                lock_tiers.early_exit_penalty_bps
+            );
+            for (index, tier) in lock_tiers.tiers.iter().enumerate() {
+                println!(
+                    "  tier {index}: {} days at {} bps",
+                    tier.duration / 86_400,
+                    tier.multiplier_bps
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn list_stakers(&self, owner: Option<Pubkey>) -> Result<()> {
+        let mut filters = vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+            0,
+            UserStakeInfo::DISCRIMINATOR.to_vec(),
+        ))];
+        if let Some(owner) = owner {
+            filters.push(RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                8,
+                owner.to_bytes().to_vec(),
+            )));
+        }
+        let accounts = self.rpc.get_program_accounts_with_config(
+            &client::PROGRAM_ID,
+            RpcProgramAccountsConfig {
+                filters: Some(filters),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    ..RpcAccountInfoConfig::default()
+                },
+                ..RpcProgramAccountsConfig::default()
+            },
+        )?;
+
+        let mut positions = accounts
+            .iter()
+            .map(|(_, account)| client::decode_user_stake(&account.data))
+            .collect::<anchor_lang::Result<Vec<_>>>()?;
+        positions.sort_by_key(|position| (position.owner, position.index));
+
+        let mut total = 0u64;
+        for position in &positions {
+            let stake = &position.stake_info;
+            total += stake.amount;
+            println!(
+                "{} #{}: {} locked until {} at {} bps",
+                position.owner,
+                position.index,
+                format_tokens(stake.amount),
+                stake.start_time + stake.lock_duration,
+                stake.multiplier_bps
+            );
+        }
+        println!(
+            "{} positions, {} staked",
+            positions.len(),
+            format_tokens(total)
+        );
+        Ok(())
+    }
+
+    fn inspect_vaa(&self, vaa: &str) -> Result<()> {
+        let vaa = Vaa::parse(&decode_bytes(vaa)?)?;
+        let message = client::decode_bridge_message(&vaa.payload)?;
+        let core_bridge = self.bridge_config.core_bridge()?;
+
+        println!("version: {}", vaa.version);
+        println!("guardian set: {}", vaa.guardian_set_index);
+        println!("signatures: {}", vaa.signatures);
+        println!("hash: 0x{}", hex::encode(vaa.hash));
+        println!(
+            "emitter: {} 0x{}",
+            self.bridge_config.chain_name(vaa.emitter_chain),
+            hex::encode(vaa.emitter_address)
+        );
+        println!("sequence: {}", vaa.sequence);
+        println!("timestamp: {}", vaa.timestamp);
+        println!("consistency level: {}", vaa.consistency_level);
+        println!("message id: 0x{}", hex::encode(message.id));
+        println!("source manager: 0x{}", hex::encode(message.source_manager));
+        println!(
+            "recipient manager: 0x{}",
+            hex::encode(message.recipient_manager)
+        );
+        println!("sender: 0x{}", hex::encode(message.sender));
+        println!(
+            "amount: {}",
+            format_vaa_amount(message.amount.amount, message.amount.decimals)?
+        );
+        println!("source token: 0x{}", hex::encode(message.source_token));
+        println!(
+            "recipient: {} on {}",
+            Pubkey::new_from_array(message.recipient),
+            self.bridge_config.chain_name(message.recipient_chain)
+        );
+
+        let posted_vaa = pda::posted_vaa(&core_bridge, &vaa.hash);
+        let posted = self.account_data(&posted_vaa)?.is_some();
+        println!(
+            "posted vaa: {posted_vaa} ({})",
+            if posted { "posted" } else { "not posted" }
+        );
+
+        let emitter = match self.account_data(&pda::foreign_emitter(vaa.emitter_chain))? {
+            Some(data) => {
+                let registered = client::decode_foreign_emitter(&data)?;
+                if registered.address == vaa.emitter_address {
+                    "registered"
+                } else {
+                    "does not match the registered emitter"
+                }
+            }
+            None => "no emitter registered for the chain",
+        };
+        println!("emitter status: {emitter}");
+
+        let claim = pda::claim(vaa.emitter_chain, &vaa.emitter_address, vaa.sequence);
+        let status = match self.account_data(&claim)? {
+            Some(data) => {
+                let claim = client::decode_claim(&data)?;
+                if claim.is_queued() {
+                    format!("queued until {}", claim.release_time)
+                } else if claim.redeemed {
+                    "redeemed".to_string()
+                } else {
+                    "not redeemed".to_string()
+                }
+            }
+            None => "not redeemed".to_string(),
+        };
+        println!("status: {status}");
+        Ok(())
+    }
+
+    fn redeem_vaa(&self, vaa: &str) -> Result<()> {
+        let vaa = Vaa::parse(&decode_bytes(vaa)?)?;
+        let message = client::decode_bridge_message(&vaa.payload)?;
+        let amount = format_vaa_amount(message.amount.amount, message.amount.decimals)?;
+        let core_bridge = self.bridge_config.core_bridge()?;
+        if self
+            .account_data(&pda::posted_vaa(&core_bridge, &vaa.hash))?
+            .is_none()
+        {
+            bail!(
+                "the VAA has not been posted to the core bridge yet; \
+                 verify its signatures and post it before redeeming"
+            );
+        }
+
+        let recipient = Pubkey::new_from_array(message.recipient);
+        let create_recipient = create_associated_token_account_idempotent(
+            &self.authority(),
+            &recipient,
+            &pda::mint(),
+            &anchor_spl::token_2022::ID,
+        );
+        let bridge_in = instructions::bridge_in(
+            &self.authority(),
+            &core_bridge,
+            vaa.hash,
+            vaa.emitter_chain,
+            vaa.emitter_address,
+            vaa.sequence,
+            &message,
+        );
+        self.send(&[create_recipient, bridge_in], &[])?;
+
+        let claim = self.fetch(
+            &pda::claim(vaa.emitter_chain, &vaa.emitter_address, vaa.sequence),
+            client::decode_claim,
+        )?;
+        if claim.is_queued() {
+            println!(
+                "{amount} exceeds the inbound capacity; queued until {}",
+                claim.release_time
+            );
+        } else {
+            println!("redeemed {amount} to {}", claim.recipient);
+        }
+        Ok(())
+    }
+
+    fn show_rate_limits(&self) -> Result<()> {
+        let now = self.rpc.get_block_time(self.rpc.get_slot()?)?;
+        let mut chains: Vec<_> = self.bridge_config.wormhole.chain_ids.iter().collect();
+        chains.sort_by_key(|(_, id)| **id);
+
+        for (name, &chain) in chains {
+            let Some(data) = self.account_data(&pda::foreign_emitter(chain))? else {
+                continue;
+            };
+            let peer = client::decode_foreign_emitter(&data)?;
+            println!(
+                "{name}: outbound {} of {}, inbound {} of {}",
+                format_tokens(peer.outbound.capacity_at(now)),
+                format_tokens(peer.outbound.limit),
+                format_tokens(peer.inbound.capacity_at(now)),
+                format_tokens(peer.inbound.limit)
+            );
+        }
+        Ok(())
+    }
+
+    fn list_queued(&self) -> Result<()> {
+        let accounts = self.rpc.get_program_accounts_with_config(
+            &client::PROGRAM_ID,
+            RpcProgramAccountsConfig {
+                filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                    0,
+                    VaaClaim::DISCRIMINATOR.to_vec(),
+                ))]),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    ..RpcAccountInfoConfig::default()
+                },
+                ..RpcProgramAccountsConfig::default()
+            },
+        )?;
+
+        let mut queued = Vec::new();
+        for (_, account) in &accounts {
+            let claim = client::decode_claim(&account.data)?;
+            if claim.is_queued() {
+                queued.push(claim);
+            }
+        }
+        queued.sort_by_key(|claim| claim.release_time);
+
+        for claim in &queued {
+            println!(
+                "{} #{}: {} to {} releasable at {}",
+                self.bridge_config.chain_name(claim.emitter_chain),
+                claim.sequence,
+                format_tokens(claim.amount),
+                claim.recipient,
+                claim.release_time
+            );
+        }
+        println!("{} queued transfers", queued.len());
+        Ok(())
+    }
+
+    fn release_inbound(&self, vaa: &str) -> Result<()> {
+        let vaa = Vaa::parse(&decode_bytes(vaa)?)?;
+        let claim = self.fetch(
+            &pda::claim(vaa.emitter_chain, &vaa.emitter_address, vaa.sequence),
+            client::decode_claim,
+        )?;
+        if !claim.is_queued() {
+            bail!("the transfer is not queued");
+        }
+        self.send(
+            &[instructions::release_inbound_transfer(
+                &self.authority(),
+                &self.cosigners(),
+                &claim,
+            )],
+            &[],
+        )?;
+        println!(
+            "released {} to {}",
+            format_tokens(claim.amount),
+            claim.recipient
+        );
+        Ok(())
+    }
+
+    /// Finds the refund record of the transfer posted with `sequence`; the
+    /// records are keyed by message account, so this scans for the sequence.
+    fn outbound_transfer(&self, sequence: u64) -> Result<OutboundTransfer> {
+        // Discriminator, sender, amount and recipient chain
+        const SEQUENCE_OFFSET: usize = 8 + 32 + 8 + 2;
+        let accounts = self.rpc.get_program_accounts_with_config(
+            &client::PROGRAM_ID,
+            RpcProgramAccountsConfig {
+                filters: Some(vec![
+                    RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                        0,
+                        OutboundTransfer::DISCRIMINATOR.to_vec(),
+                    )),
+                    RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                        SEQUENCE_OFFSET,
+                        sequence.to_le_bytes().to_vec(),
+                    )),
+                ]),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    ..RpcAccountInfoConfig::default()
+                },
+                ..RpcProgramAccountsConfig::default()
+            },
+        )?;
+        let (_, account) = accounts
+            .first()
+            .ok_or_else(|| anyhow!("no outbound transfer with sequence {sequence}"))?;
+        Ok(client::decode_outbound_transfer(&account.data)?)
+    }
+
+    fn refund_transfer(&self, vaa: &str, sender: Option<&str>) -> Result<()> {
+        let vaa = Vaa::parse(&decode_bytes(vaa)?)?;
+        let rejection = client::decode_transfer_rejected(&vaa.payload)?;
+        let core_bridge = self.bridge_config.core_bridge()?;
+        if self
+            .account_data(&pda::posted_vaa(&core_bridge, &vaa.hash))?
+            .is_none()
+        {
+            bail!("the VAA has not been posted to the core bridge yet");
+        }
+
+        // Outbound message ids are the Wormhole sequence, left-padded
+        let sequence = u64::from_be_bytes(rejection.id[24..].try_into()?);
+        let transfer = self.outbound_transfer(sequence)?;
+        if transfer.refunded {
+            bail!("transfer {sequence} has already been refunded");
+        }
+        let sender = sender.map(read_keypair).transpose()?;
+        let sender_key = sender
+            .as_ref()
+            .map_or(self.authority(), |sender| sender.pubkey());
+        if sender_key != transfer.sender {
+            bail!("transfer {sequence} was sent by {}", transfer.sender);
+        }
+
+        let create_sender_token = create_associated_token_account_idempotent(
+            &self.authority(),
+            &transfer.sender,
+            &pda::mint(),
+            &anchor_spl::token_2022::ID,
+        );
+        let refund = instructions::refund_transfer(&core_bridge, vaa.hash, &transfer);
+        let signers: Vec<&Keypair> = sender.iter().collect();
+        self.send(&[create_sender_token, refund], &signers)?;
+        println!(
+            "refunded {} to {} (rejected with reason {})",
+            format_tokens(transfer.amount),
+            transfer.sender,
+            rejection.reason
+        );
+        Ok(())
+    }
+}
+
+/// Signs `payload` with the mock guardian set; needs neither an RPC endpoint
+/// nor the authority keypair.
+fn sign_vaa(
+    bridge_config: &BridgeConfig,
+    payload: &str,
+    chain: &str,
+    emitter: Option<String>,
+    sequence: u64,
+    guardians: u8,
+    guardian_set_index: u32,
+) -> Result<()> {
+    let chain_id = bridge_config.chain_id(chain)?;
+    let emitter = match emitter {
+        Some(emitter) => parse_emitter_address(&emitter)?,
+        None => parse_emitter_address(BridgeConfig::ntt_address(
+            &bridge_config.wormhole.ntt.transceivers,
+            chain,
+            "transceiver",
+        )?)?,
+    };
+
+    let guardians = MockGuardianSet::new(guardian_set_index, guardians);
+    let body = VaaBody::new(chain_id, emitter, sequence, decode_bytes(payload)?);
+    let vaa = guardians.sign(body);
+    for (index, address) in guardians.addresses().iter().enumerate() {
+        println!("guardian {index}: 0x{}", hex::encode(address));
+    }
+    println!("hash: 0x{}", hex::encode(vaa.body.hash()));
+    println!("vaa: {}", hex::encode(vaa.serialize()));
+    Ok(())
+}
+
+/// Fields of a wire-format VAA the tool works with.
+struct Vaa {
+    version: u8,
+    guardian_set_index: u32,
+    signatures: usize,
+    timestamp: u32,
+    emitter_chain: u16,
+    emitter_address: [u8; 32],
+    sequence: u64,
+    consistency_level: u8,
+    payload: Vec<u8>,
+    hash: [u8; 32],
+}
+
+impl Vaa {
+    const SIGNATURE_LEN: usize = 66;
+    const BODY_HEADER_LEN: usize = 4 + 4 + 2 + 32 + 8 + 1;
+
+    fn parse(bytes: &[u8]) -> Result<Self> {
+        let truncated = || anyhow!("truncated VAA");
+        let signatures = *bytes.get(5).ok_or_else(truncated)? as usize;
+        let body = bytes
+            .get(6 + signatures * Self::SIGNATURE_LEN..)
+            .filter(|body| body.len() >= Self::BODY_HEADER_LEN)
+            .ok_or_else(truncated)?;
+
+        Ok(Self {
+            version: bytes[0],
+            guardian_set_index: u32::from_be_bytes(bytes[1..5].try_into()?),
+            signatures,
+            timestamp: u32::from_be_bytes(body[0..4].try_into()?),
+            emitter_chain: u16::from_be_bytes(body[8..10].try_into()?),
+            emitter_address: body[10..42].try_into()?,
+            sequence: u64::from_be_bytes(body[42..50].try_into()?),
+            consistency_level: body[50],
+            payload: body[Self::BODY_HEADER_LEN..].to_vec(),
+            hash: keccak::hash(body).to_bytes(),
+        })
+    }
+}
+
+/// Accepts hex (with or without `0x`) or base64.
+fn decode_bytes(input: &str) -> Result<Vec<u8>> {
+    let input = input.trim();
+    if let Ok(bytes) = hex::decode(input.trim_start_matches("0x")) {
+        return Ok(bytes);
+    }
+    use base64::Engine;
+    base64::engine::general_purpose::STANDARD
+        .decode(input)
+        .context("VAA is neither hex nor base64")
+}
+
+/// Left-pads 20-byte EVM addresses to the 32-byte Wormhole format; Solana
+/// addresses are accepted in base58.
+fn parse_emitter_address(address: &str) -> Result<[u8; 32]> {
+    if let Ok(pubkey) = address.parse::<Pubkey>() {
+        return Ok(pubkey.to_bytes());
+    }
+    let bytes = hex::decode(address.trim_start_matches("0x"))
+        .with_context(|| format!("invalid emitter address {address}"))?;
+    if bytes.len() > 32 {
+        bail!("emitter address {address} is longer than 32 bytes");
+    }
+    let mut padded = [0u8; 32];
+    padded[32 - bytes.len()..].copy_from_slice(&bytes);
+    Ok(padded)
+}
+
+fn format_tokens(amount: u64) -> String {
+    format_amount(amount, DECIMALS)
+}
+
+fn format_amount(amount: u64, decimals: u8) -> String {
+    let unit = 10u64.pow(decimals as u32);
+    format!(
+        "{}.{:0width$}",
+        amount / unit,
+        amount % unit,
+        width = decimals as usize
+    )
+}
+
+/// Formats a VAA amount, whose decimals come from the sending chain and are
+/// only trusted up to what a transfer carries.
+fn format_vaa_amount(amount: u64, decimals: u8) -> Result<String> {
+    if decimals > TRIMMED_DECIMALS {
+        bail!("the VAA amount has {decimals} decimals, more than the {TRIMMED_DECIMALS} a transfer carries");
+    }
+    Ok(format_amount(amount, decimals))
+}
+
+fn read_keypair(path: &str) -> Result<Keypair> {
+    let path = match path.strip_prefix("~/") {
+        Some(rest) => PathBuf::from(std::env::var("HOME")?).join(rest),
+        None => PathBuf::from(path),
+    };
+    read_keypair_file(&path).map_err(|err| anyhow!("reading {}: {err}", path.display()))
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    if let Command::SignVaa {
+        payload,
+        chain,
+        emitter,
+        sequence,
+        guardians,
+        guardian_set_index,
+    } = cli.command
+    {
+        return sign_vaa(
+            &BridgeConfig::load(&cli.config)?,
+            &payload,
+            &chain,
+            emitter,
+            sequence,
+            guardians,
+            guardian_set_index,
+        );
+    }
+
+    let admin = Admin {
+        rpc: RpcClient::new_with_commitment(cli.url, CommitmentConfig::confirmed()),
+        bridge_config: BridgeConfig::load(&cli.config)?,
+        authority: read_keypair(&cli.keypair)?,
+        cosigners: cli
+            .cosigners
+            .iter()
+            .map(|path| read_keypair(path))
+            .collect::<Result<_>>()?,
+    };
+
+    match cli.command {
+        Command::Initialize {
+            treasury_owner,
+            development_owner,
+            marketing_owner,
+            team_owner,
+        } => admin.initialize(
+            treasury_owner,
+            [development_owner, marketing_owner, team_owner],
+        ),
+        Command::Pause => admin
+            .send(
+                &[instructions::pause(&admin.authority(), &admin.cosigners())],
+                &[],
+            )
+            .map(drop),
+        Command::Unpause => admin
+            .send(
+                &[instructions::unpause(
+                    &admin.authority(),
+                    &admin.cosigners(),
+                )],
+                &[],
+            )
+            .map(drop),
+        Command::Set { parameter } => admin.set(parameter),
+        Command::ExecuteChange { id } => admin.execute_change(id),
+        Command::RegisterEmitter {
+            chain,
+            address,
+            manager,
+            decimals,
+        } => admin.register_emitter(&chain, address, manager, decimals),
+        Command::ShowConfig => admin.show_config(),
+        Command::ListStakers { owner } => admin.list_stakers(owner),
+        Command::InspectVaa { vaa } => admin.inspect_vaa(&vaa),
+        Command::RedeemVaa { vaa } => admin.redeem_vaa(&vaa),
+        Command::ShowRateLimits => admin.show_rate_limits(),
+        Command::ListQueued => admin.list_queued(),
+        Command::ReleaseInbound { vaa } => admin.release_inbound(&vaa),
+        Command::RefundTransfer { vaa, sender } => admin.refund_transfer(&vaa, sender.as_deref()),
+        Command::SignVaa { .. } => unreachable!("handled before connecting"),
+    }
+}
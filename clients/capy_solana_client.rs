@@ -0,0 +1,1040 @@
+//! Typed client for the `capy_solana_token` program.
+//!
+//! Instruction builders wrap the account and argument structs Anchor
+//! generates from the program (the same definitions the IDL is built from),
+//! so they cannot drift from the on-chain interface. The crate also exposes
+//! PDA helpers, account deserializers and decoders for `BridgeMessage`
+//! payloads in posted Wormhole messages and VAAs.
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{
+    bpf_loader_upgradeable, instruction::Instruction, system_program, sysvar,
+};
+use anchor_lang::{AccountDeserialize, InstructionData, ToAccountMetas};
+use anchor_spl::associated_token::{self, get_associated_token_address_with_program_id};
+use anchor_spl::token_2022::spl_token_2022;
+use anchor_spl::token_interface::Mint;
+use capy_solana_token::{
+    accounts, instruction, BridgeMessage, ForeignEmitter, LockTier, LockTiers, OutboundTransfer,
+    ParameterChange, PendingChange, StakingPool, TokenConfig, TransferRejected, UserStakeInfo,
+    UserVestingInfo, VaaClaim, WormholeConfig, CLAIM_SEED, CONFIG_SEED, FOREIGN_EMITTER_SEED,
+    LOCK_TIERS_SEED, MINT_AUTHORITY_SEED, MINT_SEED, OUTBOUND_TRANSFER_SEED, PENDING_CHANGE_SEED,
+    REWARD_VAULT_SEED, STAKE_SEED, STAKE_VAULT_SEED, STAKING_POOL_SEED, VAULT_AUTHORITY_SEED,
+    VESTING_ESCROW_SEED, VESTING_SEED,
+};
+use capy_solana_transfer_hook::{EXEMPTION_SEED, EXTRA_ACCOUNT_METAS_SEED, HOOK_CONFIG_SEED};
+use wormhole_anchor_sdk::wormhole;
+
+#[cfg(feature = "mock")]
+pub mod mock_wormhole;
+
+pub use capy_solana_token::ID as PROGRAM_ID;
+pub use capy_solana_transfer_hook::ID as TRANSFER_HOOK_PROGRAM_ID;
+
+/// PDA derivation for the program, the transfer hook and the Wormhole
+/// accounts the program uses.
+pub mod pda {
+    use super::*;
+
+    fn find(seeds: &[&[u8]]) -> Pubkey {
+        Pubkey::find_program_address(seeds, &PROGRAM_ID).0
+    }
+
+    pub fn config() -> Pubkey {
+        find(&[CONFIG_SEED])
+    }
+
+    /// ProgramData account holding the program's upgrade authority.
+    pub fn program_data() -> Pubkey {
+        Pubkey::find_program_address(&[PROGRAM_ID.as_ref()], &bpf_loader_upgradeable::ID).0
+    }
+
+    pub fn mint() -> Pubkey {
+        find(&[MINT_SEED])
+    }
+
+    pub fn mint_authority() -> Pubkey {
+        find(&[MINT_AUTHORITY_SEED])
+    }
+
+    pub fn vault_authority() -> Pubkey {
+        find(&[VAULT_AUTHORITY_SEED])
+    }
+
+    pub fn stake_vault() -> Pubkey {
+        find(&[STAKE_VAULT_SEED])
+    }
+
+    pub fn reward_vault() -> Pubkey {
+        find(&[REWARD_VAULT_SEED])
+    }
+
+    pub fn staking_pool() -> Pubkey {
+        find(&[STAKING_POOL_SEED])
+    }
+
+    pub fn lock_tiers() -> Pubkey {
+        find(&[LOCK_TIERS_SEED])
+    }
+
+    pub fn user_stake(owner: &Pubkey, index: u32) -> Pubkey {
+        find(&[STAKE_SEED, owner.as_ref(), &index.to_le_bytes()])
+    }
+
+    /// Vesting record of a beneficiary token account.
+    pub fn user_vesting(wallet: &Pubkey) -> Pubkey {
+        find(&[VESTING_SEED, wallet.as_ref()])
+    }
+
+    pub fn vesting_escrow(wallet: &Pubkey) -> Pubkey {
+        find(&[VESTING_ESCROW_SEED, wallet.as_ref()])
+    }
+
+    pub fn foreign_emitter(chain: u16) -> Pubkey {
+        find(&[FOREIGN_EMITTER_SEED, &chain.to_be_bytes()])
+    }
+
+    pub fn claim(emitter_chain: u16, emitter_address: &[u8; 32], sequence: u64) -> Pubkey {
+        find(&[
+            CLAIM_SEED,
+            &emitter_chain.to_be_bytes(),
+            emitter_address,
+            &sequence.to_be_bytes(),
+        ])
+    }
+
+    /// Refund record of the outbound transfer posted in `message`.
+    pub fn outbound_transfer(message: &Pubkey) -> Pubkey {
+        find(&[OUTBOUND_TRANSFER_SEED, message.as_ref()])
+    }
+
+    pub fn pending_change(id: u64) -> Pubkey {
+        find(&[PENDING_CHANGE_SEED, &id.to_le_bytes()])
+    }
+
+    /// Wormhole emitter of the program.
+    pub fn emitter() -> Pubkey {
+        find(&[wormhole::SEED_PREFIX_EMITTER])
+    }
+
+    pub fn wormhole_bridge(wormhole_program: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&[b"Bridge"], wormhole_program).0
+    }
+
+    pub fn wormhole_fee_collector(wormhole_program: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&[b"fee_collector"], wormhole_program).0
+    }
+
+    pub fn wormhole_sequence(wormhole_program: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&[b"Sequence", emitter().as_ref()], wormhole_program).0
+    }
+
+    pub fn posted_vaa(wormhole_program: &Pubkey, vaa_hash: &[u8; 32]) -> Pubkey {
+        Pubkey::find_program_address(
+            &[wormhole::SEED_PREFIX_POSTED_VAA, vaa_hash],
+            wormhole_program,
+        )
+        .0
+    }
+
+    pub fn extra_account_meta_list() -> Pubkey {
+        Pubkey::find_program_address(
+            &[EXTRA_ACCOUNT_METAS_SEED, mint().as_ref()],
+            &TRANSFER_HOOK_PROGRAM_ID,
+        )
+        .0
+    }
+
+    pub fn hook_config() -> Pubkey {
+        Pubkey::find_program_address(
+            &[HOOK_CONFIG_SEED, mint().as_ref()],
+            &TRANSFER_HOOK_PROGRAM_ID,
+        )
+        .0
+    }
+
+    pub fn exemption(owner: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(
+            &[EXEMPTION_SEED, mint().as_ref(), owner.as_ref()],
+            &TRANSFER_HOOK_PROGRAM_ID,
+        )
+        .0
+    }
+
+    /// Associated Token-2022 account of `owner` for the CAPYAI mint.
+    pub fn token_account(owner: &Pubkey) -> Pubkey {
+        get_associated_token_address_with_program_id(owner, &mint(), &spl_token_2022::ID)
+    }
+}
+
+/// Accounts the transfer hook needs appended to any transfer of the mint
+/// between token accounts owned by `source_owner` and `destination_owner`.
+pub fn transfer_hook_accounts(
+    source_owner: &Pubkey,
+    destination_owner: &Pubkey,
+) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new_readonly(TRANSFER_HOOK_PROGRAM_ID, false),
+        AccountMeta::new_readonly(pda::extra_account_meta_list(), false),
+        AccountMeta::new_readonly(pda::hook_config(), false),
+        AccountMeta::new_readonly(pda::exemption(source_owner), false),
+        AccountMeta::new_readonly(pda::exemption(destination_owner), false),
+    ]
+}
+
+/// Remaining accounts carrying the extra approvals of an M-of-N authority.
+fn cosigner_accounts(cosigners: &[Pubkey]) -> Vec<AccountMeta> {
+    cosigners
+        .iter()
+        .map(|cosigner| AccountMeta::new_readonly(*cosigner, true))
+        .collect()
+}
+
+fn program_instruction(
+    accounts: impl ToAccountMetas,
+    remaining_accounts: Vec<AccountMeta>,
+    data: impl InstructionData,
+) -> Instruction {
+    let mut metas = accounts.to_account_metas(None);
+    metas.extend(remaining_accounts);
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: metas,
+        data: data.data(),
+    }
+}
+
+/// Wallets that receive the allocations minted by `initialize`.
+pub struct AllocationWallets {
+    pub treasury_owner: Pubkey,
+    pub development_wallet: Pubkey,
+    pub marketing_wallet: Pubkey,
+    pub team_wallet: Pubkey,
+}
+
+/// Instruction builders, one per program instruction.
+pub mod instructions {
+    use super::*;
+
+    pub fn initialize_mint(authority: &Pubkey) -> Instruction {
+        program_instruction(
+            accounts::InitializeMint {
+                authority: *authority,
+                program_data: pda::program_data(),
+                mint: pda::mint(),
+                mint_authority: pda::mint_authority(),
+                config: pda::config(),
+                extra_account_meta_list: pda::extra_account_meta_list(),
+                hook_config: pda::hook_config(),
+                transfer_hook_program: TRANSFER_HOOK_PROGRAM_ID,
+                token_program: spl_token_2022::ID,
+                system_program: system_program::ID,
+            },
+            Vec::new(),
+            instruction::InitializeMint {},
+        )
+    }
+
+    pub fn initialize(
+        authority: &Pubkey,
+        wallets: &AllocationWallets,
+        wormhole_config: WormholeConfig,
+    ) -> Instruction {
+        program_instruction(
+            accounts::Initialize {
+                mint: pda::mint(),
+                mint_authority: pda::mint_authority(),
+                vault_authority: pda::vault_authority(),
+                authority: *authority,
+                program_data: pda::program_data(),
+                treasury_owner: wallets.treasury_owner,
+                treasury_wallet: pda::token_account(&wallets.treasury_owner),
+                development_wallet: wallets.development_wallet,
+                marketing_wallet: wallets.marketing_wallet,
+                team_wallet: wallets.team_wallet,
+                stake_vault: pda::stake_vault(),
+                reward_vault: pda::reward_vault(),
+                development_escrow: pda::vesting_escrow(&wallets.development_wallet),
+                marketing_escrow: pda::vesting_escrow(&wallets.marketing_wallet),
+                team_escrow: pda::vesting_escrow(&wallets.team_wallet),
+                config: pda::config(),
+                staking_pool: pda::staking_pool(),
+                lock_tiers: pda::lock_tiers(),
+                team_vesting: pda::user_vesting(&wallets.team_wallet),
+                development_vesting: pda::user_vesting(&wallets.development_wallet),
+                marketing_vesting: pda::user_vesting(&wallets.marketing_wallet),
+                hook_config: pda::hook_config(),
+                vault_exemption: pda::exemption(&pda::vault_authority()),
+                treasury_exemption: pda::exemption(&wallets.treasury_owner),
+                transfer_hook_program: TRANSFER_HOOK_PROGRAM_ID,
+                token_program: spl_token_2022::ID,
+                associated_token_program: associated_token::ID,
+                system_program: system_program::ID,
+            },
+            Vec::new(),
+            instruction::Initialize { wormhole_config },
+        )
+    }
+
+    pub fn stake(owner: &Pubkey, index: u32, tier: u8, amount: u64) -> Instruction {
+        program_instruction(
+            accounts::Stake {
+                owner: *owner,
+                from: pda::token_account(owner),
+                stake_vault: pda::stake_vault(),
+                reward_vault: pda::reward_vault(),
+                vault_authority: pda::vault_authority(),
+                user_stake: pda::user_stake(owner, index),
+                lock_tiers: pda::lock_tiers(),
+                staking_pool: pda::staking_pool(),
+                mint: pda::mint(),
+                config: pda::config(),
+                token_program: spl_token_2022::ID,
+                system_program: system_program::ID,
+            },
+            transfer_hook_accounts(owner, &pda::vault_authority()),
+            instruction::Stake {
+                index,
+                tier,
+                amount,
+            },
+        )
+    }
+
+    /// `treasury_owner` is needed for the hook accounts of an early-exit
+    /// penalty transfer.
+    pub fn unstake(
+        owner: &Pubkey,
+        treasury_owner: &Pubkey,
+        index: u32,
+        amount: u64,
+    ) -> Instruction {
+        let mut remaining_accounts = transfer_hook_accounts(&pda::vault_authority(), owner);
+        remaining_accounts.push(AccountMeta::new_readonly(
+            pda::exemption(treasury_owner),
+            false,
+        ));
+        program_instruction(
+            accounts::Unstake {
+                owner: *owner,
+                stake_vault: pda::stake_vault(),
+                owner_token: pda::token_account(owner),
+                reward_vault: pda::reward_vault(),
+                treasury_wallet: pda::token_account(treasury_owner),
+                vault_authority: pda::vault_authority(),
+                user_stake: pda::user_stake(owner, index),
+                lock_tiers: pda::lock_tiers(),
+                staking_pool: pda::staking_pool(),
+                mint: pda::mint(),
+                config: pda::config(),
+                token_program: spl_token_2022::ID,
+            },
+            remaining_accounts,
+            instruction::Unstake { index, amount },
+        )
+    }
+
+    pub fn claim_rewards(owner: &Pubkey, index: u32) -> Instruction {
+        program_instruction(
+            accounts::ClaimRewards {
+                owner: *owner,
+                owner_token: pda::token_account(owner),
+                reward_vault: pda::reward_vault(),
+                vault_authority: pda::vault_authority(),
+                user_stake: pda::user_stake(owner, index),
+                staking_pool: pda::staking_pool(),
+                mint: pda::mint(),
+                config: pda::config(),
+                token_program: spl_token_2022::ID,
+            },
+            transfer_hook_accounts(&pda::vault_authority(), owner),
+            instruction::ClaimRewards { index },
+        )
+    }
+
+    /// `token_accounts` are harvested of their withheld fees before the mint
+    /// balance is withdrawn to the treasury.
+    pub fn collect_transfer_fees(
+        treasury_wallet: &Pubkey,
+        token_accounts: &[Pubkey],
+    ) -> Instruction {
+        program_instruction(
+            accounts::CollectTransferFees {
+                mint: pda::mint(),
+                treasury_wallet: *treasury_wallet,
+                config: pda::config(),
+                token_program: spl_token_2022::ID,
+            },
+            token_accounts
+                .iter()
+                .map(|account| AccountMeta::new(*account, false))
+                .collect(),
+            instruction::CollectTransferFees {},
+        )
+    }
+
+    /// `message` is a new keypair the core bridge creates the message
+    /// account at, which also keys the transfer's refund record; it must
+    /// sign next to `owner`, who also pays the Wormhole message fee in SOL.
+    /// `treasury_owner` receives the bridge fee.
+    pub fn bridge_out(
+        owner: &Pubkey,
+        message: &Pubkey,
+        treasury_owner: &Pubkey,
+        wormhole_program: &Pubkey,
+        amount: u64,
+        recipient_chain: u16,
+        recipient: [u8; 32],
+    ) -> Instruction {
+        program_instruction(
+            accounts::BridgeOut {
+                owner: *owner,
+                from: pda::token_account(owner),
+                mint: pda::mint(),
+                config: pda::config(),
+                foreign_emitter: pda::foreign_emitter(recipient_chain),
+                treasury_wallet: pda::token_account(treasury_owner),
+                wormhole_program: *wormhole_program,
+                wormhole_bridge: pda::wormhole_bridge(wormhole_program),
+                message: *message,
+                emitter: pda::emitter(),
+                sequence: pda::wormhole_sequence(wormhole_program),
+                payer: *owner,
+                fee_collector: pda::wormhole_fee_collector(wormhole_program),
+                outbound_transfer: pda::outbound_transfer(message),
+                clock: sysvar::clock::ID,
+                rent: sysvar::rent::ID,
+                system_program: system_program::ID,
+                token_program: spl_token_2022::ID,
+            },
+            transfer_hook_accounts(owner, treasury_owner),
+            instruction::BridgeOut {
+                amount,
+                recipient_chain,
+                recipient,
+            },
+        )
+    }
+
+    /// Redeems a VAA the core bridge has already posted. `message` is the
+    /// payload of that VAA and decides the recipient token account.
+    pub fn bridge_in(
+        payer: &Pubkey,
+        wormhole_program: &Pubkey,
+        vaa_hash: [u8; 32],
+        emitter_chain: u16,
+        emitter_address: [u8; 32],
+        sequence: u64,
+        message: &BridgeMessage,
+    ) -> Instruction {
+        let recipient_owner = Pubkey::new_from_array(message.recipient);
+        program_instruction(
+            accounts::BridgeIn {
+                payer: *payer,
+                recipient: pda::token_account(&recipient_owner),
+                mint: pda::mint(),
+                config: pda::config(),
+                mint_authority: pda::mint_authority(),
+                posted_vaa: pda::posted_vaa(wormhole_program, &vaa_hash),
+                foreign_emitter: pda::foreign_emitter(emitter_chain),
+                claim: pda::claim(emitter_chain, &emitter_address, sequence),
+                wormhole_program: *wormhole_program,
+                token_program: spl_token_2022::ID,
+                system_program: system_program::ID,
+            },
+            Vec::new(),
+            instruction::BridgeIn { vaa_hash },
+        )
+    }
+
+    /// Mints a transfer `bridge_in` queued in `claim`. Before the release
+    /// time `caller` must be the authority, with `cosigners` in M-of-N mode.
+    pub fn release_inbound_transfer(
+        caller: &Pubkey,
+        cosigners: &[Pubkey],
+        claim: &VaaClaim,
+    ) -> Instruction {
+        program_instruction(
+            accounts::ReleaseInboundTransfer {
+                caller: *caller,
+                recipient: claim.recipient,
+                mint: pda::mint(),
+                config: pda::config(),
+                mint_authority: pda::mint_authority(),
+                claim: pda::claim(claim.emitter_chain, &claim.emitter_address, claim.sequence),
+                token_program: spl_token_2022::ID,
+            },
+            cosigner_accounts(cosigners),
+            instruction::ReleaseInboundTransfer {},
+        )
+    }
+
+    /// Re-mints a rejected transfer to its sender, who must sign. `vaa_hash`
+    /// identifies the posted `TransferRejected` VAA from the peer.
+    pub fn refund_transfer(
+        wormhole_program: &Pubkey,
+        vaa_hash: [u8; 32],
+        transfer: &OutboundTransfer,
+    ) -> Instruction {
+        program_instruction(
+            accounts::RefundTransfer {
+                sender: transfer.sender,
+                sender_token: pda::token_account(&transfer.sender),
+                mint: pda::mint(),
+                config: pda::config(),
+                mint_authority: pda::mint_authority(),
+                posted_vaa: pda::posted_vaa(wormhole_program, &vaa_hash),
+                foreign_emitter: pda::foreign_emitter(transfer.recipient_chain),
+                outbound_transfer: pda::outbound_transfer(&transfer.message),
+                wormhole_program: *wormhole_program,
+                token_program: spl_token_2022::ID,
+            },
+            Vec::new(),
+            instruction::RefundTransfer { vaa_hash },
+        )
+    }
+
+    pub fn set_rate_limits(
+        authority: &Pubkey,
+        cosigners: &[Pubkey],
+        chain: u16,
+        outbound_limit: u64,
+        inbound_limit: u64,
+    ) -> Instruction {
+        program_instruction(
+            accounts::SetRateLimits {
+                authority: *authority,
+                config: pda::config(),
+                foreign_emitter: pda::foreign_emitter(chain),
+            },
+            cosigner_accounts(cosigners),
+            instruction::SetRateLimits {
+                chain,
+                outbound_limit,
+                inbound_limit,
+            },
+        )
+    }
+
+    /// Returns `RateLimitCapacity` as return data when simulated.
+    pub fn remaining_capacity(chain: u16) -> Instruction {
+        program_instruction(
+            accounts::RemainingCapacity {
+                foreign_emitter: pda::foreign_emitter(chain),
+            },
+            Vec::new(),
+            instruction::RemainingCapacity { chain },
+        )
+    }
+
+    fn update_config(authority: &Pubkey) -> accounts::UpdateConfig {
+        accounts::UpdateConfig {
+            authority: *authority,
+            config: pda::config(),
+        }
+    }
+
+    pub fn pause(authority: &Pubkey, cosigners: &[Pubkey]) -> Instruction {
+        program_instruction(
+            update_config(authority),
+            cosigner_accounts(cosigners),
+            instruction::Pause {},
+        )
+    }
+
+    pub fn unpause(authority: &Pubkey, cosigners: &[Pubkey]) -> Instruction {
+        program_instruction(
+            update_config(authority),
+            cosigner_accounts(cosigners),
+            instruction::Unpause {},
+        )
+    }
+
+    pub fn set_treasury_wallet(
+        authority: &Pubkey,
+        cosigners: &[Pubkey],
+        new_treasury_wallet: &Pubkey,
+    ) -> Instruction {
+        program_instruction(
+            accounts::SetTreasuryWallet {
+                authority: *authority,
+                new_treasury_wallet: *new_treasury_wallet,
+                config: pda::config(),
+            },
+            cosigner_accounts(cosigners),
+            instruction::SetTreasuryWallet {},
+        )
+    }
+
+    pub fn set_min_stake_amount(
+        authority: &Pubkey,
+        cosigners: &[Pubkey],
+        new_amount: u64,
+    ) -> Instruction {
+        program_instruction(
+            update_config(authority),
+            cosigner_accounts(cosigners),
+            instruction::SetMinStakeAmount { new_amount },
+        )
+    }
+
+    pub fn set_wormhole_config(
+        authority: &Pubkey,
+        cosigners: &[Pubkey],
+        wormhole_config: WormholeConfig,
+    ) -> Instruction {
+        program_instruction(
+            update_config(authority),
+            cosigner_accounts(cosigners),
+            instruction::SetWormholeConfig { wormhole_config },
+        )
+    }
+
+    pub fn set_lock_tiers(
+        authority: &Pubkey,
+        cosigners: &[Pubkey],
+        tiers: Vec<LockTier>,
+        early_exit_penalty_bps: u16,
+    ) -> Instruction {
+        program_instruction(
+            accounts::SetLockTiers {
+                authority: *authority,
+                config: pda::config(),
+                lock_tiers: pda::lock_tiers(),
+            },
+            cosigner_accounts(cosigners),
+            instruction::SetLockTiers {
+                tiers,
+                early_exit_penalty_bps,
+            },
+        )
+    }
+
+    /// Exempts `owner` from the transfer hook's limit; `authority` also pays
+    /// for the exemption account.
+    pub fn set_exemption(
+        authority: &Pubkey,
+        cosigners: &[Pubkey],
+        owner: &Pubkey,
+        exempt: bool,
+    ) -> Instruction {
+        program_instruction(
+            accounts::SetExemption {
+                authority: *authority,
+                payer: *authority,
+                config: pda::config(),
+                hook_config: pda::hook_config(),
+                exemption: pda::exemption(owner),
+                transfer_hook_program: TRANSFER_HOOK_PROGRAM_ID,
+                system_program: system_program::ID,
+            },
+            cosigner_accounts(cosigners),
+            instruction::SetExemption {
+                owner: *owner,
+                exempt,
+            },
+        )
+    }
+
+    pub fn set_max_transfer_amount(
+        authority: &Pubkey,
+        cosigners: &[Pubkey],
+        amount: u64,
+    ) -> Instruction {
+        program_instruction(
+            accounts::SetMaxTransferAmount {
+                authority: *authority,
+                config: pda::config(),
+                hook_config: pda::hook_config(),
+                transfer_hook_program: TRANSFER_HOOK_PROGRAM_ID,
+            },
+            cosigner_accounts(cosigners),
+            instruction::SetMaxTransferAmount { amount },
+        )
+    }
+
+    /// Registers the NTT peer of `chain`: its Wormhole transceiver
+    /// (`address`), its manager and the decimals of its token.
+    pub fn register_emitter(
+        authority: &Pubkey,
+        cosigners: &[Pubkey],
+        chain: u16,
+        address: [u8; 32],
+        manager: [u8; 32],
+        token_decimals: u8,
+    ) -> Instruction {
+        program_instruction(
+            accounts::RegisterEmitter {
+                authority: *authority,
+                config: pda::config(),
+                foreign_emitter: pda::foreign_emitter(chain),
+                system_program: system_program::ID,
+            },
+            cosigner_accounts(cosigners),
+            instruction::RegisterEmitter {
+                chain,
+                address,
+                manager,
+                token_decimals,
+            },
+        )
+    }
+
+    /// `next_change_id` is `TokenConfig::next_change_id` at the time of
+    /// scheduling.
+    pub fn schedule_change(
+        authority: &Pubkey,
+        cosigners: &[Pubkey],
+        next_change_id: u64,
+        change: ParameterChange,
+    ) -> Instruction {
+        program_instruction(
+            accounts::ScheduleChange {
+                authority: *authority,
+                config: pda::config(),
+                pending_change: pda::pending_change(next_change_id),
+                system_program: system_program::ID,
+            },
+            cosigner_accounts(cosigners),
+            instruction::ScheduleChange { change },
+        )
+    }
+
+    pub fn execute_change(id: u64, scheduled_by: &Pubkey) -> Instruction {
+        program_instruction(
+            accounts::ExecuteChange {
+                mint: pda::mint(),
+                config: pda::config(),
+                pending_change: pda::pending_change(id),
+                scheduled_by: *scheduled_by,
+                staking_pool: pda::staking_pool(),
+                token_program: spl_token_2022::ID,
+            },
+            Vec::new(),
+            instruction::ExecuteChange {},
+        )
+    }
+
+    pub fn cancel_change(
+        authority: &Pubkey,
+        cosigners: &[Pubkey],
+        id: u64,
+        scheduled_by: &Pubkey,
+    ) -> Instruction {
+        program_instruction(
+            accounts::CancelChange {
+                authority: *authority,
+                config: pda::config(),
+                pending_change: pda::pending_change(id),
+                scheduled_by: *scheduled_by,
+            },
+            cosigner_accounts(cosigners),
+            instruction::CancelChange {},
+        )
+    }
+
+    pub fn propose_authority(
+        authority: &Pubkey,
+        cosigners: &[Pubkey],
+        new_authority: Pubkey,
+    ) -> Instruction {
+        program_instruction(
+            update_config(authority),
+            cosigner_accounts(cosigners),
+            instruction::ProposeAuthority { new_authority },
+        )
+    }
+
+    pub fn accept_authority(pending_authority: &Pubkey) -> Instruction {
+        program_instruction(
+            accounts::AcceptAuthority {
+                pending_authority: *pending_authority,
+                config: pda::config(),
+            },
+            Vec::new(),
+            instruction::AcceptAuthority {},
+        )
+    }
+
+    pub fn set_authority_signers(
+        authority: &Pubkey,
+        cosigners: &[Pubkey],
+        signers: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Instruction {
+        program_instruction(
+            update_config(authority),
+            cosigner_accounts(cosigners),
+            instruction::SetAuthoritySigners { signers, threshold },
+        )
+    }
+
+    fn claim_vesting(
+        beneficiary: &Pubkey,
+        beneficiary_token: &Pubkey,
+    ) -> (accounts::ClaimVesting, Vec<AccountMeta>) {
+        (
+            accounts::ClaimVesting {
+                beneficiary: *beneficiary,
+                beneficiary_token: *beneficiary_token,
+                user_vesting: pda::user_vesting(beneficiary_token),
+                escrow: pda::vesting_escrow(beneficiary_token),
+                mint: pda::mint(),
+                config: pda::config(),
+                vault_authority: pda::vault_authority(),
+                token_program: spl_token_2022::ID,
+            },
+            transfer_hook_accounts(&pda::vault_authority(), beneficiary),
+        )
+    }
+
+    pub fn claim_team_tokens(beneficiary: &Pubkey, beneficiary_token: &Pubkey) -> Instruction {
+        let (accounts, remaining_accounts) = claim_vesting(beneficiary, beneficiary_token);
+        program_instruction(
+            accounts,
+            remaining_accounts,
+            instruction::ClaimTeamTokens {},
+        )
+    }
+
+    pub fn claim_development_tokens(
+        beneficiary: &Pubkey,
+        beneficiary_token: &Pubkey,
+    ) -> Instruction {
+        let (accounts, remaining_accounts) = claim_vesting(beneficiary, beneficiary_token);
+        program_instruction(
+            accounts,
+            remaining_accounts,
+            instruction::ClaimDevelopmentTokens {},
+        )
+    }
+
+    pub fn claim_marketing_tokens(beneficiary: &Pubkey, beneficiary_token: &Pubkey) -> Instruction {
+        let (accounts, remaining_accounts) = claim_vesting(beneficiary, beneficiary_token);
+        program_instruction(
+            accounts,
+            remaining_accounts,
+            instruction::ClaimMarketingTokens {},
+        )
+    }
+}
+
+/// Deserializes any program account, checking its discriminator.
+pub fn decode_account<T: AccountDeserialize>(data: &[u8]) -> Result<T> {
+    T::try_deserialize(&mut &data[..])
+}
+
+pub fn decode_config(data: &[u8]) -> Result<TokenConfig> {
+    decode_account(data)
+}
+
+/// Decodes the Token-2022 mint; its `supply` is the circulating supply,
+/// which bridging burns and mints change.
+pub fn decode_mint(data: &[u8]) -> Result<Mint> {
+    decode_account(data)
+}
+
+pub fn decode_user_stake(data: &[u8]) -> Result<UserStakeInfo> {
+    decode_account(data)
+}
+
+pub fn decode_user_vesting(data: &[u8]) -> Result<UserVestingInfo> {
+    decode_account(data)
+}
+
+pub fn decode_staking_pool(data: &[u8]) -> Result<StakingPool> {
+    decode_account(data)
+}
+
+pub fn decode_lock_tiers(data: &[u8]) -> Result<LockTiers> {
+    decode_account(data)
+}
+
+pub fn decode_pending_change(data: &[u8]) -> Result<PendingChange> {
+    decode_account(data)
+}
+
+/// Decodes a registered peer, including its rate limits.
+pub fn decode_foreign_emitter(data: &[u8]) -> Result<ForeignEmitter> {
+    decode_account(data)
+}
+
+/// Decodes a VAA redemption record, including queued inbound transfers.
+pub fn decode_claim(data: &[u8]) -> Result<VaaClaim> {
+    decode_account(data)
+}
+
+pub fn decode_outbound_transfer(data: &[u8]) -> Result<OutboundTransfer> {
+    decode_account(data)
+}
+
+/// Metadata the core bridge stores in front of posted messages and VAAs.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PostedMeta {
+    pub version: u8,
+    pub consistency_level: u8,
+    pub timestamp: u32,
+    pub signature_set: Pubkey,
+    pub posted_timestamp: u32,
+    pub nonce: u32,
+    pub sequence: u64,
+    pub emitter_chain: u16,
+    pub emitter_address: [u8; 32],
+}
+
+/// Decodes a `BridgeMessage` payload as carried in a Wormhole message.
+pub fn decode_bridge_message(payload: &[u8]) -> Result<BridgeMessage> {
+    BridgeMessage::try_from_slice(payload)
+        .map_err(|_| error!(anchor_lang::error::ErrorCode::AccountDidNotDeserialize))
+}
+
+/// Decodes a `TransferRejected` payload a peer sends for a refused transfer.
+pub fn decode_transfer_rejected(payload: &[u8]) -> Result<TransferRejected> {
+    TransferRejected::try_from_slice(payload)
+        .map_err(|_| error!(anchor_lang::error::ErrorCode::AccountDidNotDeserialize))
+}
+
+fn decode_posted(discriminator: &[u8], data: &[u8]) -> Result<(PostedMeta, BridgeMessage)> {
+    if !data.starts_with(discriminator) {
+        return Err(error!(
+            anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch
+        ));
+    }
+    let mut data = &data[discriminator.len()..];
+    let meta = PostedMeta::deserialize(&mut data)
+        .map_err(|_| error!(anchor_lang::error::ErrorCode::AccountDidNotDeserialize))?;
+    let payload = Vec::<u8>::deserialize(&mut data)
+        .map_err(|_| error!(anchor_lang::error::ErrorCode::AccountDidNotDeserialize))?;
+    Ok((meta, decode_bridge_message(&payload)?))
+}
+
+/// Decodes the message account `bridge_out` posts to the core bridge.
+pub fn decode_posted_message(data: &[u8]) -> Result<(PostedMeta, BridgeMessage)> {
+    decode_posted(b"msg", data)
+}
+
+/// Decodes a posted VAA account as read by `bridge_in`.
+pub fn decode_posted_vaa(data: &[u8]) -> Result<(PostedMeta, BridgeMessage)> {
+    decode_posted(b"vaa", data)
+}
+
+/// Decodes the `BridgeMessage` in the payload of a raw wire-format VAA.
+pub fn decode_vaa_payload(vaa: &[u8]) -> Result<BridgeMessage> {
+    const SIGNATURE_LEN: usize = 66;
+    const BODY_HEADER_LEN: usize = 4 + 4 + 2 + 32 + 8 + 1;
+
+    let signatures = *vaa.get(5).ok_or(error!(
+        anchor_lang::error::ErrorCode::AccountDidNotDeserialize
+    ))? as usize;
+    let payload_start = 6 + signatures * SIGNATURE_LEN + BODY_HEADER_LEN;
+    let payload = vaa.get(payload_start..).ok_or(error!(
+        anchor_lang::error::ErrorCode::AccountDidNotDeserialize
+    ))?;
+    decode_bridge_message(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use capy_solana_token::TrimmedAmount;
+
+    fn message() -> BridgeMessage {
+        BridgeMessage {
+            source_manager: [0xdd; 32],
+            recipient_manager: PROGRAM_ID.to_bytes(),
+            id: BridgeMessage::id_from_sequence(7),
+            sender: [0x22; 32],
+            amount: TrimmedAmount::new(123_456_789, 8),
+            source_token: [0x11; 32],
+            recipient: [0x33; 32],
+            recipient_chain: wormhole::CHAIN_ID_SOLANA,
+        }
+    }
+
+    fn meta() -> PostedMeta {
+        PostedMeta {
+            version: 1,
+            consistency_level: 1,
+            timestamp: 1_700_000_000,
+            signature_set: Pubkey::new_from_array([0x55; 32]),
+            posted_timestamp: 1_700_000_001,
+            nonce: 42,
+            sequence: 7,
+            emitter_chain: 2,
+            emitter_address: [0xee; 32],
+        }
+    }
+
+    /// Account data in the core bridge layout.
+    fn posted(discriminator: &[u8], payload: &[u8]) -> Vec<u8> {
+        let mut data = discriminator.to_vec();
+        meta().serialize(&mut data).unwrap();
+        payload.to_vec().serialize(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn bridge_messages_round_trip() {
+        let payload = message().try_to_vec().unwrap();
+        assert_eq!(payload.len(), BridgeMessage::LEN);
+        assert_eq!(decode_bridge_message(&payload).unwrap(), message());
+        assert!(decode_bridge_message(&payload[..payload.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn transfer_rejections_round_trip() {
+        let rejection = TransferRejected {
+            source_manager: [0xdd; 32],
+            recipient_manager: PROGRAM_ID.to_bytes(),
+            id: BridgeMessage::id_from_sequence(7),
+            reason: 3,
+        };
+        let payload = rejection.try_to_vec().unwrap();
+        assert_eq!(payload.len(), TransferRejected::LEN);
+        assert_eq!(decode_transfer_rejected(&payload).unwrap(), rejection);
+        // A transfer is not a rejection
+        let transfer = message().try_to_vec().unwrap();
+        assert!(decode_transfer_rejected(&transfer).is_err());
+    }
+
+    #[test]
+    fn posted_accounts_round_trip() {
+        let payload = message().try_to_vec().unwrap();
+
+        let vaa = posted(b"vaa", &payload);
+        assert_eq!(decode_posted_vaa(&vaa).unwrap(), (meta(), message()));
+        let posted_message = posted(b"msg", &payload);
+        assert_eq!(
+            decode_posted_message(&posted_message).unwrap(),
+            (meta(), message())
+        );
+
+        // Messages and VAAs are not interchangeable
+        assert!(decode_posted_vaa(&posted_message).is_err());
+        assert!(decode_posted_message(&vaa).is_err());
+        assert!(decode_posted_vaa(&vaa[..vaa.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn vaa_payloads_are_found_after_the_signatures() {
+        let payload = message().try_to_vec().unwrap();
+        let meta = meta();
+        let signatures = 2u8;
+
+        let mut vaa = vec![1];
+        vaa.extend_from_slice(&0u32.to_be_bytes());
+        vaa.push(signatures);
+        vaa.extend(vec![0xab; signatures as usize * 66]);
+        vaa.extend_from_slice(&meta.timestamp.to_be_bytes());
+        vaa.extend_from_slice(&meta.nonce.to_be_bytes());
+        vaa.extend_from_slice(&meta.emitter_chain.to_be_bytes());
+        vaa.extend_from_slice(&meta.emitter_address);
+        vaa.extend_from_slice(&meta.sequence.to_be_bytes());
+        vaa.push(meta.consistency_level);
+        vaa.extend_from_slice(&payload);
+
+        assert_eq!(decode_vaa_payload(&vaa).unwrap(), message());
+        assert!(decode_vaa_payload(&vaa[..5]).is_err());
+        assert!(decode_vaa_payload(&vaa[..vaa.len() - payload.len()]).is_err());
+    }
+}
@@ -0,0 +1,317 @@
+//! Local Wormhole guardian set for generating VAAs without a Wormhole network,
+//! enabled by the `mock` feature.
+//!
+//! `MockGuardianSet` signs VAAs in the real wire format with locally held
+//! secp256k1 keys. The program tests use it to produce posted VAA accounts
+//! for `bridge_in`, and `capy-admin sign-vaa` uses it to sign payloads for
+//! dry runs against a core bridge whose guardian set is
+//! `MockGuardianSet::addresses`.
+
+use anchor_lang::prelude::{AnchorSerialize, Pubkey};
+use anchor_lang::solana_program::keccak;
+use capy_solana_token::{BridgeMessage, TransferRejected};
+use libsecp256k1::{Message, PublicKey, RecoveryId, SecretKey, Signature};
+use wormhole_anchor_sdk::wormhole;
+
+use crate::PostedMeta;
+
+pub const VAA_VERSION: u8 = 1;
+pub const SIGNATURE_LEN: usize = 66; // Guardian index + r, s and recovery id
+pub const BODY_HEADER_LEN: usize = 4 + 4 + 2 + 32 + 8 + 1;
+const GUARDIAN_KEY_SEED: &[u8] = b"capy-mock-guardian";
+
+/// Account data in the core bridge layout: discriminator, metadata and the
+/// length-prefixed payload.
+pub fn posted_account_data(discriminator: &[u8], meta: &PostedMeta, payload: &[u8]) -> Vec<u8> {
+    let mut data = discriminator.to_vec();
+    meta.serialize(&mut data).unwrap();
+    payload.to_vec().serialize(&mut data).unwrap();
+    data
+}
+
+/// The signed part of a VAA.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VaaBody {
+    pub timestamp: u32,
+    pub nonce: u32,
+    pub emitter_chain: u16,
+    pub emitter_address: [u8; 32],
+    pub sequence: u64,
+    pub consistency_level: u8,
+    pub payload: Vec<u8>,
+}
+
+impl VaaBody {
+    /// Body carrying `payload` from a foreign emitter.
+    pub fn new(
+        emitter_chain: u16,
+        emitter_address: [u8; 32],
+        sequence: u64,
+        payload: Vec<u8>,
+    ) -> Self {
+        Self {
+            timestamp: 1_700_000_000,
+            nonce: 0,
+            emitter_chain,
+            emitter_address,
+            sequence,
+            consistency_level: 1,
+            payload,
+        }
+    }
+
+    /// Body carrying a `BridgeMessage` from a foreign CAPYAI emitter.
+    pub fn bridge_message(
+        emitter_chain: u16,
+        emitter_address: [u8; 32],
+        sequence: u64,
+        message: &BridgeMessage,
+    ) -> Self {
+        Self::new(
+            emitter_chain,
+            emitter_address,
+            sequence,
+            message.try_to_vec().unwrap(),
+        )
+    }
+
+    /// Body carrying a `TransferRejected` from a foreign CAPYAI emitter.
+    pub fn transfer_rejected(
+        emitter_chain: u16,
+        emitter_address: [u8; 32],
+        sequence: u64,
+        rejection: &TransferRejected,
+    ) -> Self {
+        Self::new(
+            emitter_chain,
+            emitter_address,
+            sequence,
+            rejection.try_to_vec().unwrap(),
+        )
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut body = Vec::with_capacity(BODY_HEADER_LEN + self.payload.len());
+        body.extend_from_slice(&self.timestamp.to_be_bytes());
+        body.extend_from_slice(&self.nonce.to_be_bytes());
+        body.extend_from_slice(&self.emitter_chain.to_be_bytes());
+        body.extend_from_slice(&self.emitter_address);
+        body.extend_from_slice(&self.sequence.to_be_bytes());
+        body.push(self.consistency_level);
+        body.extend_from_slice(&self.payload);
+        body
+    }
+
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < BODY_HEADER_LEN {
+            return None;
+        }
+        Some(Self {
+            timestamp: u32::from_be_bytes(bytes[0..4].try_into().ok()?),
+            nonce: u32::from_be_bytes(bytes[4..8].try_into().ok()?),
+            emitter_chain: u16::from_be_bytes(bytes[8..10].try_into().ok()?),
+            emitter_address: bytes[10..42].try_into().ok()?,
+            sequence: u64::from_be_bytes(bytes[42..50].try_into().ok()?),
+            consistency_level: bytes[50],
+            payload: bytes[BODY_HEADER_LEN..].to_vec(),
+        })
+    }
+
+    /// Keccak hash of the body; the core bridge seeds the posted VAA
+    /// account with it.
+    pub fn hash(&self) -> [u8; 32] {
+        keccak::hash(&self.serialize()).to_bytes()
+    }
+
+    /// Double keccak hash of the body, which the guardians sign.
+    pub fn digest(&self) -> [u8; 32] {
+        keccak::hash(&self.hash()).to_bytes()
+    }
+}
+
+/// A guardian signature: the signer's index in the set and `r || s || v`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GuardianSignature {
+    pub guardian_index: u8,
+    pub signature: [u8; 65],
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignedVaa {
+    pub guardian_set_index: u32,
+    pub signatures: Vec<GuardianSignature>,
+    pub body: VaaBody,
+}
+
+impl SignedVaa {
+    /// Serializes the VAA in the wire format the guardian network emits.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut vaa = vec![VAA_VERSION];
+        vaa.extend_from_slice(&self.guardian_set_index.to_be_bytes());
+        vaa.push(self.signatures.len() as u8);
+        for signature in &self.signatures {
+            vaa.push(signature.guardian_index);
+            vaa.extend_from_slice(&signature.signature);
+        }
+        vaa.extend_from_slice(&self.body.serialize());
+        vaa
+    }
+
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < 6 || bytes[0] != VAA_VERSION {
+            return None;
+        }
+        let guardian_set_index = u32::from_be_bytes(bytes[1..5].try_into().ok()?);
+        let count = bytes[5] as usize;
+        let body_start = 6 + count * SIGNATURE_LEN;
+        if bytes.len() < body_start {
+            return None;
+        }
+
+        let signatures = bytes[6..body_start]
+            .chunks_exact(SIGNATURE_LEN)
+            .map(|chunk| GuardianSignature {
+                guardian_index: chunk[0],
+                signature: chunk[1..].try_into().unwrap(),
+            })
+            .collect();
+        Some(Self {
+            guardian_set_index,
+            signatures,
+            body: VaaBody::parse(&bytes[body_start..])?,
+        })
+    }
+
+    /// Checks that a quorum of distinct guardians from `guardian_addresses`
+    /// signed the body, the way the core bridge verifies signatures.
+    pub fn verify(&self, guardian_addresses: &[[u8; 20]]) -> bool {
+        let message = Message::parse(&self.body.digest());
+        let mut last_index = None;
+        for signature in &self.signatures {
+            // Signatures must be sorted by guardian index without repeats
+            if last_index.is_some_and(|last| signature.guardian_index <= last) {
+                return false;
+            }
+            last_index = Some(signature.guardian_index);
+
+            let Some(expected) = guardian_addresses.get(signature.guardian_index as usize) else {
+                return false;
+            };
+            let Ok(rs) = Signature::parse_standard_slice(&signature.signature[..64]) else {
+                return false;
+            };
+            let Ok(recovery_id) = RecoveryId::parse(signature.signature[64]) else {
+                return false;
+            };
+            match libsecp256k1::recover(&message, &rs, &recovery_id) {
+                Ok(key) if eth_address(&key) == *expected => {}
+                _ => return false,
+            }
+        }
+        self.signatures.len() >= quorum(guardian_addresses.len())
+    }
+
+    /// Address and data of the account the core bridge creates once the VAA
+    /// has been verified and posted.
+    pub fn posted_account(&self) -> PostedVaaAccount {
+        let hash = self.body.hash();
+        let meta = PostedMeta {
+            version: VAA_VERSION,
+            consistency_level: self.body.consistency_level,
+            timestamp: self.body.timestamp,
+            signature_set: Pubkey::new_from_array(keccak::hashv(&[b"signature_set", &hash]).0),
+            posted_timestamp: self.body.timestamp,
+            nonce: self.body.nonce,
+            sequence: self.body.sequence,
+            emitter_chain: self.body.emitter_chain,
+            emitter_address: self.body.emitter_address,
+        };
+        PostedVaaAccount {
+            address: Pubkey::find_program_address(
+                &[wormhole::SEED_PREFIX_POSTED_VAA, &hash],
+                &wormhole::program::ID,
+            )
+            .0,
+            hash,
+            data: posted_account_data(b"vaa", &meta, &self.body.payload),
+        }
+    }
+}
+
+pub struct PostedVaaAccount {
+    pub address: Pubkey,
+    pub hash: [u8; 32],
+    pub data: Vec<u8>,
+}
+
+/// Number of signatures the core bridge requires for `guardians` keys.
+pub fn quorum(guardians: usize) -> usize {
+    guardians * 2 / 3 + 1
+}
+
+/// Ethereum-style address of a guardian key, as stored in the guardian set.
+pub fn eth_address(key: &PublicKey) -> [u8; 20] {
+    let hash = keccak::hash(&key.serialize()[1..]).to_bytes();
+    hash[12..].try_into().unwrap()
+}
+
+pub struct MockGuardianSet {
+    pub index: u32,
+    keys: Vec<SecretKey>,
+}
+
+impl MockGuardianSet {
+    /// Deterministic set of `count` guardians, so dry runs and tests see the
+    /// same addresses every time.
+    pub fn new(index: u32, count: u8) -> Self {
+        let keys = (0..count)
+            .map(|i| {
+                let seed = keccak::hashv(&[GUARDIAN_KEY_SEED, &[i]]).to_bytes();
+                SecretKey::parse(&seed).expect("valid guardian key")
+            })
+            .collect();
+        Self { index, keys }
+    }
+
+    pub fn from_secret_keys(index: u32, keys: Vec<SecretKey>) -> Self {
+        Self { index, keys }
+    }
+
+    pub fn addresses(&self) -> Vec<[u8; 20]> {
+        self.keys
+            .iter()
+            .map(|key| eth_address(&PublicKey::from_secret_key(key)))
+            .collect()
+    }
+
+    /// Signs `body` with a quorum of guardians.
+    pub fn sign(&self, body: VaaBody) -> SignedVaa {
+        let signers: Vec<u8> = (0..quorum(self.keys.len()) as u8).collect();
+        self.sign_with(body, &signers)
+    }
+
+    /// Signs `body` with the guardians at `signers`, which lets tests build
+    /// VAAs below quorum.
+    pub fn sign_with(&self, body: VaaBody, signers: &[u8]) -> SignedVaa {
+        let message = Message::parse(&body.digest());
+        let signatures = signers
+            .iter()
+            .map(|&guardian_index| {
+                let (rs, recovery_id) =
+                    libsecp256k1::sign(&message, &self.keys[guardian_index as usize]);
+                let mut signature = [0u8; 65];
+                signature[..64].copy_from_slice(&rs.serialize());
+                signature[64] = recovery_id.serialize();
+                GuardianSignature {
+                    guardian_index,
+                    signature,
+                }
+            })
+            .collect();
+        SignedVaa {
+            guardian_set_index: self.index,
+            signatures,
+            body,
+        }
+    }
+}
@@ -1,247 +1,2005 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Mint, Token, TokenAccount};
+use anchor_lang::solana_program::{bpf_loader_upgradeable, program::invoke_signed};
+use anchor_lang::system_program::{self, CreateAccount};
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    extension::{transfer_fee::instruction::withdraw_withheld_tokens_from_accounts, ExtensionType},
+};
+use anchor_spl::token_2022::Token2022;
+use anchor_spl::token_2022_extensions::{
+    transfer_fee::{
+        self, HarvestWithheldTokensToMint, TransferFeeInitialize, TransferFeeSetTransferFee,
+        WithdrawWithheldTokensFromMint,
+    },
+    transfer_hook::{self, TransferHookInitialize},
+};
+use anchor_spl::token_interface::{self, Mint, TokenAccount};
+use capy_solana_transfer_hook::program::CapySolanaTransferHook;
+use std::io::{self, Read, Write};
 use wormhole_anchor_sdk::wormhole;
 
-declare_id!("Capyxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx");
+declare_id!("CapyTokenUQ4tMcHRwQsRVwcSaFEofhDPhzLHnZW7XyB");
+
+// Constants
+pub const INITIAL_SUPPLY: u64 = 1_000_000_000; // 1 billion tokens
+pub const LIQUIDITY_ALLOCATION: u64 = 400_000_000; // 40%
+pub const STAKING_ALLOCATION: u64 = 250_000_000; // 25%
+pub const DEVELOPMENT_ALLOCATION: u64 = 150_000_000; // 15%
+pub const MARKETING_ALLOCATION: u64 = 100_000_000; // 10%
+pub const TEAM_ALLOCATION: u64 = 100_000_000; // 10%
+pub const DECIMALS: u8 = 9;
+
+const _: () = assert!(
+    LIQUIDITY_ALLOCATION
+        + STAKING_ALLOCATION
+        + DEVELOPMENT_ALLOCATION
+        + MARKETING_ALLOCATION
+        + TEAM_ALLOCATION
+        == INITIAL_SUPPLY
+);
+
+// Vesting constants
+pub const TEAM_VESTING_DURATION: i64 = 63_072_000; // 2 years
+pub const TEAM_CLIFF_PERIOD: i64 = 31_536_000; // 1 year
+pub const DEVELOPMENT_VESTING_DURATION: i64 = 63_072_000; // 2 years
+pub const MARKETING_VESTING_PERIOD: i64 = 7_776_000; // 90 days
+pub const MARKETING_VESTING_QUARTERS: i64 = 8; // 8 quarters in 2 years
+
+// Transfer tax, enforced by the Token-2022 transfer fee extension and
+// refunded on transfers into and out of the program vaults. The transfer
+// limit is `capy_solana_transfer_hook::MAX_TRANSFER_AMOUNT`.
+pub const TRANSFER_TAX_RATE: u64 = 2; // 2%
+pub const MAX_TRANSFER_TAX_RATE: u64 = 10; // 10%
+pub const TRANSFER_HOOK_PROGRAM_ID: Pubkey = capy_solana_transfer_hook::ID;
+
+// Staking rewards
+pub const MIN_STAKE_AMOUNT: u64 = 1000; // 1000 tokens
+pub const EMISSION_DURATION: u64 = 4 * 365 * 86_400; // Staking allocation spread over 4 years
+pub const DEFAULT_EMISSION_RATE: u64 = STAKING_ALLOCATION * 1_000_000_000 / EMISSION_DURATION; // Base units per second
+pub const MAX_EMISSION_RATE: u64 = 10 * DEFAULT_EMISSION_RATE;
+pub const REWARD_PRECISION: u128 = 1_000_000_000_000; // Fixed-point scale of reward_per_token
+
+// Lock-up tiers
+pub const BPS_DENOMINATOR: u64 = 10_000; // 100%
+pub const MAX_LOCK_TIERS: usize = 8;
+pub const MAX_LOCK_MULTIPLIER_BPS: u16 = 50_000; // 5x
+pub const MAX_EARLY_EXIT_PENALTY_BPS: u16 = 5_000; // 50%
+pub const DEFAULT_EARLY_EXIT_PENALTY_BPS: u16 = 1_000; // 10%
+pub const DEFAULT_LOCK_TIERS: [LockTier; 5] = [
+    LockTier::new(0, 10_000),            // Flexible, 1x
+    LockTier::new(30 * 86_400, 12_500),  // 30 days, 1.25x
+    LockTier::new(90 * 86_400, 15_000),  // 90 days, 1.5x
+    LockTier::new(180 * 86_400, 20_000), // 180 days, 2x
+    LockTier::new(365 * 86_400, 30_000), // 365 days, 3x
+];
+
+// Timelock
+pub const TIMELOCK_DELAY: i64 = 2 * 86_400; // 48 hours between scheduling and executing a change
+
+// Bridge rate limits
+pub const RATE_LIMIT_DURATION: i64 = 86_400; // Capacity refills to the limit over 24 hours
+pub const DEFAULT_RATE_LIMIT: u64 = 10_000_000; // 10M tokens per window and direction
+pub const INBOUND_QUEUE_DELAY: i64 = 86_400; // Queued inbound transfers release after 24 hours
+
+// Bridge fee, taken from outbound transfers and paid to the treasury
+pub const MAX_BRIDGE_FEE_BPS: u16 = 1_000; // 10%
+
+// Authority
+pub const MAX_AUTHORITY_SIGNERS: usize = 10; // Upper bound of the M-of-N signer set
+
+// PDA seeds
+pub const CONFIG_SEED: &[u8] = b"config";
+pub const MINT_SEED: &[u8] = b"mint";
+pub const MINT_AUTHORITY_SEED: &[u8] = b"mint_authority";
+pub const VAULT_AUTHORITY_SEED: &[u8] = b"vault_authority";
+pub const STAKE_VAULT_SEED: &[u8] = b"stake_vault";
+pub const REWARD_VAULT_SEED: &[u8] = b"reward_vault";
+pub const STAKE_SEED: &[u8] = b"stake";
+pub const VESTING_SEED: &[u8] = b"vesting";
+pub const VESTING_ESCROW_SEED: &[u8] = b"vesting_escrow";
+pub const CLAIM_SEED: &[u8] = b"claim";
+pub const FOREIGN_EMITTER_SEED: &[u8] = b"foreign_emitter";
+pub const PENDING_CHANGE_SEED: &[u8] = b"pending_change";
+pub const LOCK_TIERS_SEED: &[u8] = b"lock_tiers";
+pub const STAKING_POOL_SEED: &[u8] = b"staking_pool";
+pub const OUTBOUND_TRANSFER_SEED: &[u8] = b"outbound_transfer";
+
+// Wormhole NTT wire format
+pub const NTT_TRANSCEIVER_PREFIX: [u8; 4] = [0x99, 0x45, 0xFF, 0x10]; // Wormhole transceiver
+pub const NTT_TRANSFER_PREFIX: [u8; 4] = [0x99, 0x4E, 0x54, 0x54]; // Native token transfer
+pub const TRIMMED_DECIMALS: u8 = 8; // Most decimals an NTT amount carries
+pub const TRANSFER_REJECTED_PREFIX: [u8; 4] = [0x99, 0x52, 0x4A, 0x54]; // CAPYAI rejection
 
 #[program]
 pub mod capy_solana_token {
     use super::*;
 
-    // Constants
-    pub const INITIAL_SUPPLY: u64 = 1_000_000_000; // 1 billion tokens
-    pub const LIQUIDITY_ALLOCATION: u64 = 400_000_000; // 40%
-    pub const STAKING_ALLOCATION: u64 = 250_000_000; // 25%
-    pub const DEVELOPMENT_ALLOCATION: u64 = 150_000_000; // 15%
-    pub const MARKETING_ALLOCATION: u64 = 100_000_000; // 10%
-    pub const TEAM_ALLOCATION: u64 = 100_000_000; // 10%
-
-    // Vesting constants
-    pub const TEAM_VESTING_DURATION: i64 = 63_072_000; // 2 years
-    pub const TEAM_CLIFF_PERIOD: i64 = 31_536_000; // 1 year
-    pub const DEVELOPMENT_VESTING_DURATION: i64 = 63_072_000; // 2 years
-    pub const MARKETING_VESTING_PERIOD: i64 = 7_776_000; // 90 days
-
-    // Transfer limits and tax
-    pub const MAX_TRANSFER_AMOUNT: u64 = 1_000_000 * 1_000_000_000; // 1M tokens
-    pub const TRANSFER_TAX_RATE: u64 = 2; // 2%
-
-    #[state]
-    pub struct CapySolanaToken {
-        pub mint: Pubkey,
-        pub authority: Pubkey,
-        pub treasury_wallet: Pubkey,
-        pub development_wallet: Pubkey,
-        pub marketing_wallet: Pubkey,
-        pub team_wallet: Pubkey,
-        pub total_supply: u64,
-        pub team_vesting_start: i64,
-        pub development_vesting_start: i64,
-        pub marketing_vesting_start: i64,
-        pub paused: bool,
-        pub wormhole_config: WormholeConfig,
-    }
-
-    #[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
-    pub struct WormholeConfig {
-        pub bridge: Pubkey,
-        pub message_fee: u64,
-        pub consistency_level: u8,
-    }
-
-    #[derive(AnchorSerialize, AnchorDeserialize, Clone)]
-    pub struct StakeInfo {
-        pub amount: u64,
-        pub start_time: i64,
-        pub last_claim_time: i64,
-    }
-
-    #[derive(AnchorSerialize, AnchorDeserialize, Clone)]
-    pub struct VestingInfo {
-        pub total_amount: u64,
-        pub claimed_amount: u64,
-        pub start_time: i64,
-        pub duration: i64,
-        pub cliff_period: Option<i64>,
-    }
-
-    #[account]
-    pub struct UserStakeInfo {
-        pub owner: Pubkey,
-        pub stake_info: StakeInfo,
-    }
-
-    #[account]
-    pub struct UserVestingInfo {
-        pub owner: Pubkey,
-        pub vesting_info: VestingInfo,
-    }
-
-    impl CapySolanaToken {
-        pub fn initialize(
-            ctx: Context<Initialize>,
-            wormhole_config: WormholeConfig,
-        ) -> Result<()> {
-            let token = &mut ctx.accounts.token;
-            token.authority = ctx.accounts.authority.key();
-            token.mint = ctx.accounts.mint.key();
-            token.treasury_wallet = ctx.accounts.treasury_wallet.key();
-            token.development_wallet = ctx.accounts.development_wallet.key();
-            token.marketing_wallet = ctx.accounts.marketing_wallet.key();
-            token.team_wallet = ctx.accounts.team_wallet.key();
-            token.total_supply = INITIAL_SUPPLY;
-            token.team_vesting_start = Clock::get()?.unix_timestamp;
-            token.development_vesting_start = Clock::get()?.unix_timestamp;
-            token.marketing_vesting_start = Clock::get()?.unix_timestamp;
-            token.wormhole_config = wormhole_config;
-            token.paused = false;
-
-            // Mint initial allocations
-            token::mint_to(
-                CpiContext::new(
-                    ctx.accounts.token_program.to_account_info(),
-                    token::MintTo {
-                        mint: ctx.accounts.mint.to_account_info(),
-                        to: ctx.accounts.treasury_wallet.to_account_info(),
-                        authority: ctx.accounts.authority.to_account_info(),
+    pub fn initialize_mint(ctx: Context<InitializeMint>) -> Result<()> {
+        // Token-2022 mint with the 2% transfer fee and the transfer hook that
+        // enforces capy_solana_transfer_hook::MAX_TRANSFER_AMOUNT. The config
+        // PDA controls both extensions and is the hook's authority, so hook
+        // settings go through `set_exemption` and `set_max_transfer_amount`.
+        let space = ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(&[
+            ExtensionType::TransferFeeConfig,
+            ExtensionType::TransferHook,
+        ])?;
+        system_program::create_account(
+            CpiContext::new_with_signer(
+                ctx.accounts.system_program.to_account_info(),
+                CreateAccount {
+                    from: ctx.accounts.authority.to_account_info(),
+                    to: ctx.accounts.mint.to_account_info(),
+                },
+                &[&[MINT_SEED, &[ctx.bumps.mint]]],
+            ),
+            Rent::get()?.minimum_balance(space),
+            space as u64,
+            &Token2022::id(),
+        )?;
+
+        let config_key = ctx.accounts.config.key();
+        transfer_fee::transfer_fee_initialize(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferFeeInitialize {
+                    token_program_id: ctx.accounts.token_program.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                },
+            ),
+            Some(&config_key),
+            Some(&config_key),
+            (TRANSFER_TAX_RATE * 100) as u16,
+            u64::MAX,
+        )?;
+
+        transfer_hook::transfer_hook_initialize(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferHookInitialize {
+                    token_program_id: ctx.accounts.token_program.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                },
+            ),
+            Some(config_key),
+            Some(TRANSFER_HOOK_PROGRAM_ID),
+        )?;
+
+        token_interface::initialize_mint2(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                token_interface::InitializeMint2 {
+                    mint: ctx.accounts.mint.to_account_info(),
+                },
+            ),
+            DECIMALS,
+            &ctx.accounts.mint_authority.key(),
+            None,
+        )?;
+
+        capy_solana_transfer_hook::cpi::initialize_extra_account_meta_list(
+            CpiContext::new_with_signer(
+                ctx.accounts.transfer_hook_program.to_account_info(),
+                capy_solana_transfer_hook::cpi::accounts::InitializeExtraAccountMetaList {
+                    payer: ctx.accounts.authority.to_account_info(),
+                    hook_authority: ctx.accounts.config.to_account_info(),
+                    extra_account_meta_list: ctx.accounts.extra_account_meta_list.to_account_info(),
+                    hook_config: ctx.accounts.hook_config.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    system_program: ctx.accounts.system_program.to_account_info(),
+                },
+                &[&[CONFIG_SEED, &[ctx.bumps.config]]],
+            ),
+            config_key,
+        )?;
+
+        Ok(())
+    }
+
+    pub fn initialize(ctx: Context<Initialize>, wormhole_config: WormholeConfig) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.authority = ctx.accounts.authority.key();
+        config.pending_authority = Pubkey::default();
+        config.authority_signers = Vec::new();
+        config.authority_threshold = 0;
+        config.mint = ctx.accounts.mint.key();
+        config.treasury_wallet = ctx.accounts.treasury_wallet.key();
+        config.development_wallet = ctx.accounts.development_wallet.key();
+        config.marketing_wallet = ctx.accounts.marketing_wallet.key();
+        config.team_wallet = ctx.accounts.team_wallet.key();
+        config.stake_vault = ctx.accounts.stake_vault.key();
+        config.reward_vault = ctx.accounts.reward_vault.key();
+        config.team_vesting_start = Clock::get()?.unix_timestamp;
+        config.development_vesting_start = Clock::get()?.unix_timestamp;
+        config.marketing_vesting_start = Clock::get()?.unix_timestamp;
+        config.wormhole_config = wormhole_config;
+        config.paused = false;
+        config.transfer_tax_rate = TRANSFER_TAX_RATE;
+        config.transfer_tax_rate_epoch = Clock::get()?.epoch;
+        config.min_stake_amount = to_base_units(MIN_STAKE_AMOUNT);
+        config.bridge_fee_bps = 0;
+        config.next_change_id = 0;
+        config.bump = ctx.bumps.config;
+        config.mint_authority_bump = ctx.bumps.mint_authority;
+        config.vault_authority_bump = ctx.bumps.vault_authority;
+
+        let now = config.team_vesting_start;
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        staking_pool.reward_per_token = 0;
+        staking_pool.total_weight = 0;
+        staking_pool.emission_rate = DEFAULT_EMISSION_RATE;
+        staking_pool.emitted = 0;
+        staking_pool.emission_cap = to_base_units(STAKING_ALLOCATION);
+        staking_pool.last_update_time = now;
+        staking_pool.bump = ctx.bumps.staking_pool;
+
+        let lock_tiers = &mut ctx.accounts.lock_tiers;
+        lock_tiers.tiers = DEFAULT_LOCK_TIERS.to_vec();
+        lock_tiers.early_exit_penalty_bps = DEFAULT_EARLY_EXIT_PENALTY_BPS;
+        lock_tiers.bump = ctx.bumps.lock_tiers;
+
+        let team_vesting = &mut ctx.accounts.team_vesting;
+        team_vesting.owner = ctx.accounts.team_wallet.key();
+        team_vesting.vesting_info = VestingInfo {
+            total_amount: to_base_units(TEAM_ALLOCATION),
+            claimed_amount: 0,
+            start_time: now,
+            duration: TEAM_VESTING_DURATION,
+            cliff_period: Some(TEAM_CLIFF_PERIOD),
+        };
+
+        let development_vesting = &mut ctx.accounts.development_vesting;
+        development_vesting.owner = ctx.accounts.development_wallet.key();
+        development_vesting.vesting_info = VestingInfo {
+            total_amount: to_base_units(DEVELOPMENT_ALLOCATION),
+            claimed_amount: 0,
+            start_time: now,
+            duration: DEVELOPMENT_VESTING_DURATION,
+            cliff_period: None,
+        };
+
+        let marketing_vesting = &mut ctx.accounts.marketing_vesting;
+        marketing_vesting.owner = ctx.accounts.marketing_wallet.key();
+        marketing_vesting.vesting_info = VestingInfo {
+            total_amount: to_base_units(MARKETING_ALLOCATION),
+            claimed_amount: 0,
+            start_time: now,
+            duration: MARKETING_VESTING_PERIOD * MARKETING_VESTING_QUARTERS,
+            cliff_period: None,
+        };
+
+        // Like the EVM constructor, exempt the program vaults and the treasury
+        // from the per-transfer limit: stake withdrawals, reward and vesting
+        // payouts and penalties routinely move more than it
+        let exempt_owners = [
+            (
+                ctx.accounts.vault_authority.key(),
+                ctx.accounts.vault_exemption.to_account_info(),
+            ),
+            (
+                ctx.accounts.treasury_owner.key(),
+                ctx.accounts.treasury_exemption.to_account_info(),
+            ),
+        ];
+        for (owner, exemption) in exempt_owners {
+            capy_solana_transfer_hook::cpi::set_exemption(
+                CpiContext::new_with_signer(
+                    ctx.accounts.transfer_hook_program.to_account_info(),
+                    capy_solana_transfer_hook::cpi::accounts::SetExemption {
+                        authority: ctx.accounts.config.to_account_info(),
+                        payer: ctx.accounts.authority.to_account_info(),
+                        hook_config: ctx.accounts.hook_config.to_account_info(),
+                        exemption,
+                        system_program: ctx.accounts.system_program.to_account_info(),
                     },
+                    &[&[CONFIG_SEED, &[ctx.bumps.config]]],
                 ),
+                owner,
+                true,
+            )?;
+        }
+
+        // Mint initial allocations: liquidity to the treasury, staking rewards
+        // to the reward vault and the vesting buckets to program-owned escrows
+        let accounts = &ctx.accounts;
+        let allocations = [
+            (
+                accounts.treasury_wallet.to_account_info(),
                 LIQUIDITY_ALLOCATION,
+            ),
+            (accounts.reward_vault.to_account_info(), STAKING_ALLOCATION),
+            (
+                accounts.development_escrow.to_account_info(),
+                DEVELOPMENT_ALLOCATION,
+            ),
+            (
+                accounts.marketing_escrow.to_account_info(),
+                MARKETING_ALLOCATION,
+            ),
+            (accounts.team_escrow.to_account_info(), TEAM_ALLOCATION),
+        ];
+        for (to, allocation) in allocations {
+            mint_tokens(
+                &accounts.token_program,
+                &accounts.mint,
+                &accounts.mint_authority,
+                to,
+                ctx.bumps.mint_authority,
+                to_base_units(allocation),
+            )?;
+        }
+
+        ctx.accounts.mint.reload()?;
+        require!(
+            ctx.accounts.mint.supply == to_base_units(INITIAL_SUPPLY),
+            TokenError::SupplyMismatch
+        );
+
+        Ok(())
+    }
+
+    pub fn stake<'info>(
+        ctx: Context<'_, '_, '_, 'info, Stake<'info>>,
+        index: u32,
+        tier: u8,
+        amount: u64,
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, TokenError::Paused);
+        require!(
+            amount >= ctx.accounts.config.min_stake_amount,
+            StakeError::BelowMinimum
+        );
+
+        // A position keeps its tier; topping up restarts the lock
+        let lock_tier = *ctx
+            .accounts
+            .lock_tiers
+            .tiers
+            .get(tier as usize)
+            .ok_or(StakeError::InvalidTier)?;
+        let stake_info = &ctx.accounts.user_stake.stake_info;
+        require!(
+            stake_info.amount == 0 || stake_info.lock_duration == lock_tier.duration,
+            StakeError::TierMismatch
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+
+        transfer_without_fee(
+            &ctx.accounts.token_program,
+            &ctx.accounts.config,
+            ctx.accounts.from.to_account_info(),
+            &ctx.accounts.mint,
+            ctx.accounts.stake_vault.to_account_info(),
+            ctx.accounts.owner.to_account_info(),
+            ctx.remaining_accounts,
+            amount,
+            &[],
+        )?;
+
+        // A top-up compounds the rewards accrued so far into the position,
+        // where the EVM stake() claims them before adding
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        staking_pool.update(now);
+        staking_pool.settle(&mut ctx.accounts.user_stake.stake_info);
+        let rewards = ctx.accounts.user_stake.stake_info.pending_rewards;
+        if rewards > 0 {
+            pay_rewards(
+                &ctx.accounts.token_program,
+                &ctx.accounts.config,
+                &ctx.accounts.reward_vault,
+                &ctx.accounts.mint,
+                &ctx.accounts.stake_vault,
+                &ctx.accounts.vault_authority,
+                ctx.remaining_accounts,
+                rewards,
+            )?;
+        }
+
+        let user_stake = &mut ctx.accounts.user_stake;
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        staking_pool.total_weight -= user_stake.stake_info.weight();
+        user_stake.owner = ctx.accounts.owner.key();
+        user_stake.index = index;
+        user_stake.stake_info.amount += amount + rewards;
+        user_stake.stake_info.start_time = now;
+        user_stake.stake_info.last_claim_time = now;
+        user_stake.stake_info.lock_duration = lock_tier.duration;
+        user_stake.stake_info.multiplier_bps = lock_tier.multiplier_bps;
+        user_stake.stake_info.pending_rewards = 0;
+        staking_pool.total_weight += user_stake.stake_info.weight();
+
+        emit!(Staked {
+            owner: ctx.accounts.owner.key(),
+            amount,
+            index,
+            compounded: rewards,
+        });
+
+        Ok(())
+    }
+
+    pub fn unstake<'info>(
+        ctx: Context<'_, '_, '_, 'info, Unstake<'info>>,
+        index: u32,
+        amount: u64,
+    ) -> Result<()> {
+        require!(amount > 0, TokenError::ZeroAmount);
+        let now = Clock::get()?.unix_timestamp;
+        let user_stake = &ctx.accounts.user_stake;
+        require!(
+            amount <= user_stake.stake_info.amount,
+            StakeError::InsufficientStake
+        );
+
+        // Leaving before the lock expires costs the early-exit penalty, or is
+        // rejected when the penalty is disabled
+        let lock_end = user_stake.stake_info.start_time + user_stake.stake_info.lock_duration;
+        let mut penalty = 0;
+        if now < lock_end {
+            let penalty_bps = ctx.accounts.lock_tiers.early_exit_penalty_bps;
+            require!(penalty_bps > 0, StakeError::LockActive);
+            penalty = (amount as u128 * penalty_bps as u128 / BPS_DENOMINATOR as u128) as u64;
+        }
+
+        // Claim rewards first
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        staking_pool.update(now);
+        staking_pool.settle(&mut ctx.accounts.user_stake.stake_info);
+        let rewards = ctx.accounts.user_stake.stake_info.pending_rewards;
+        let vault_authority_bump = ctx.accounts.config.vault_authority_bump;
+        if rewards > 0 {
+            pay_rewards(
+                &ctx.accounts.token_program,
+                &ctx.accounts.config,
+                &ctx.accounts.reward_vault,
+                &ctx.accounts.mint,
+                &ctx.accounts.owner_token,
+                &ctx.accounts.vault_authority,
+                ctx.remaining_accounts,
+                rewards,
+            )?;
+        }
+
+        transfer_without_fee(
+            &ctx.accounts.token_program,
+            &ctx.accounts.config,
+            ctx.accounts.stake_vault.to_account_info(),
+            &ctx.accounts.mint,
+            ctx.accounts.owner_token.to_account_info(),
+            ctx.accounts.vault_authority.to_account_info(),
+            ctx.remaining_accounts,
+            amount - penalty,
+            &[&[VAULT_AUTHORITY_SEED, &[vault_authority_bump]]],
+        )?;
+
+        // The remaining accounts must also cover the treasury owner's
+        // exemption PDA when a penalty is charged
+        if penalty > 0 {
+            transfer_without_fee(
+                &ctx.accounts.token_program,
+                &ctx.accounts.config,
+                ctx.accounts.stake_vault.to_account_info(),
+                &ctx.accounts.mint,
+                ctx.accounts.treasury_wallet.to_account_info(),
+                ctx.accounts.vault_authority.to_account_info(),
+                ctx.remaining_accounts,
+                penalty,
+                &[&[VAULT_AUTHORITY_SEED, &[vault_authority_bump]]],
+            )?;
+        }
+
+        let user_stake = &mut ctx.accounts.user_stake;
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        staking_pool.total_weight -= user_stake.stake_info.weight();
+        user_stake.stake_info.amount -= amount;
+        user_stake.stake_info.last_claim_time = now;
+        user_stake.stake_info.pending_rewards = 0;
+        staking_pool.total_weight += user_stake.stake_info.weight();
+        if user_stake.stake_info.amount == 0 {
+            ctx.accounts
+                .user_stake
+                .close(ctx.accounts.owner.to_account_info())?;
+        }
+
+        emit!(Unstaked {
+            owner: ctx.accounts.owner.key(),
+            amount,
+            rewards,
+            index,
+            penalty,
+        });
+
+        Ok(())
+    }
+
+    pub fn collect_transfer_fees<'info>(
+        ctx: Context<'_, '_, '_, 'info, CollectTransferFees<'info>>,
+    ) -> Result<()> {
+        let treasury_before = ctx.accounts.treasury_wallet.amount;
+
+        // Move fees withheld in the given token accounts to the mint, then
+        // send everything withheld on the mint to the treasury
+        transfer_fee::harvest_withheld_tokens_to_mint(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                HarvestWithheldTokensToMint {
+                    token_program_id: ctx.accounts.token_program.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                },
+            ),
+            ctx.remaining_accounts.to_vec(),
+        )?;
+
+        transfer_fee::withdraw_withheld_tokens_from_mint(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            WithdrawWithheldTokensFromMint {
+                token_program_id: ctx.accounts.token_program.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                destination: ctx.accounts.treasury_wallet.to_account_info(),
+                authority: ctx.accounts.config.to_account_info(),
+            },
+            &[&[CONFIG_SEED, &[ctx.accounts.config.bump]]],
+        ))?;
+
+        ctx.accounts.treasury_wallet.reload()?;
+        emit!(FeesCollected {
+            treasury_wallet: ctx.accounts.treasury_wallet.key(),
+            amount: ctx.accounts.treasury_wallet.amount - treasury_before,
+        });
+
+        Ok(())
+    }
+
+    pub fn claim_rewards<'info>(
+        ctx: Context<'_, '_, '_, 'info, ClaimRewards<'info>>,
+        index: u32,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let user_stake = &mut ctx.accounts.user_stake;
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        staking_pool.update(now);
+        staking_pool.settle(&mut user_stake.stake_info);
+        let rewards = user_stake.stake_info.pending_rewards;
+        require!(rewards > 0, StakeError::NoRewards);
+
+        user_stake.stake_info.pending_rewards = 0;
+        user_stake.stake_info.last_claim_time = now;
+
+        pay_rewards(
+            &ctx.accounts.token_program,
+            &ctx.accounts.config,
+            &ctx.accounts.reward_vault,
+            &ctx.accounts.mint,
+            &ctx.accounts.owner_token,
+            &ctx.accounts.vault_authority,
+            ctx.remaining_accounts,
+            rewards,
+        )?;
+
+        emit!(RewardsClaimed {
+            owner: ctx.accounts.owner.key(),
+            amount: rewards,
+            index,
+        });
+
+        Ok(())
+    }
+
+    pub fn bridge_out<'info>(
+        ctx: Context<'_, '_, '_, 'info, BridgeOut<'info>>,
+        amount: u64,
+        recipient_chain: u16,
+        recipient: [u8; 32],
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, TokenError::Paused);
+        require!(amount > 0, TokenError::ZeroAmount);
+
+        // The bridge fee goes to the treasury; only the rest is burned and
+        // bridged. The remaining accounts must cover the transfer hook
+        // accounts for the owner and the treasury owner when a fee is charged.
+        let fee = bridge_fee_for(&ctx.accounts.config, amount);
+
+        // The message carries the amount in the decimals both chains support,
+        // at most 8; the dust below that is not burned and stays with the owner
+        let trimmed = TrimmedAmount::trim(
+            amount - fee,
+            DECIMALS,
+            ctx.accounts.foreign_emitter.token_decimals,
+        );
+        let amount = trimmed.untrim(DECIMALS).ok_or(TokenError::AmountOverflow)?;
+        require!(amount > 0, TokenError::ZeroAmount);
+        if fee > 0 {
+            transfer_with_hook(
+                &ctx.accounts.token_program,
+                ctx.accounts.from.to_account_info(),
+                &ctx.accounts.mint,
+                ctx.accounts.treasury_wallet.to_account_info(),
+                ctx.accounts.owner.to_account_info(),
+                ctx.remaining_accounts,
+                fee,
+                &[],
+            )?;
+        }
+
+        // Outbound transfers free up inbound capacity from the same chain
+        let now = Clock::get()?.unix_timestamp;
+        let foreign_emitter = &mut ctx.accounts.foreign_emitter;
+        require!(
+            foreign_emitter.outbound.consume(amount, now),
+            TokenError::RateLimitExceeded
+        );
+        foreign_emitter.inbound.refill(amount, now);
+
+        // Burn tokens
+        token_interface::burn(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                token_interface::Burn {
+                    mint: ctx.accounts.mint.to_account_info(),
+                    from: ctx.accounts.from.to_account_info(),
+                    authority: ctx.accounts.owner.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+
+        // Post Wormhole message; the core bridge assigns the emitter's
+        // current sequence to it, which doubles as the NTT message id
+        let sequence = next_sequence(&ctx.accounts.sequence)?;
+        let message = BridgeMessage {
+            source_manager: crate::ID.to_bytes(),
+            recipient_manager: ctx.accounts.foreign_emitter.manager,
+            id: BridgeMessage::id_from_sequence(sequence),
+            sender: ctx.accounts.owner.key().to_bytes(),
+            amount: trimmed,
+            source_token: ctx.accounts.mint.key().to_bytes(),
+            recipient,
+            recipient_chain,
+        };
+
+        // Recorded by message account so the sender can be refunded if the
+        // peer rejects the transfer
+        let outbound_transfer = &mut ctx.accounts.outbound_transfer;
+        outbound_transfer.sender = ctx.accounts.owner.key();
+        outbound_transfer.amount = amount;
+        outbound_transfer.recipient_chain = recipient_chain;
+        outbound_transfer.sequence = sequence;
+        outbound_transfer.message = ctx.accounts.message.key();
+        outbound_transfer.refunded = false;
+
+        // The core bridge charges its message fee in lamports and checks
+        // that the fee collector received it before accepting the message
+        let message_fee = ctx.accounts.wormhole_bridge.fee();
+        require!(
+            message_fee <= ctx.accounts.config.wormhole_config.message_fee,
+            TokenError::WormholeFeeTooHigh
+        );
+        if message_fee > 0 {
+            system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.payer.to_account_info(),
+                        to: ctx.accounts.fee_collector.to_account_info(),
+                    },
+                ),
+                message_fee,
+            )?;
+        }
+
+        wormhole::post_message(
+            CpiContext::new_with_signer(
+                ctx.accounts.wormhole_program.to_account_info(),
+                wormhole::PostMessage {
+                    config: ctx.accounts.wormhole_bridge.to_account_info(),
+                    message: ctx.accounts.message.to_account_info(),
+                    emitter: ctx.accounts.emitter.to_account_info(),
+                    sequence: ctx.accounts.sequence.to_account_info(),
+                    payer: ctx.accounts.payer.to_account_info(),
+                    fee_collector: ctx.accounts.fee_collector.to_account_info(),
+                    clock: ctx.accounts.clock.to_account_info(),
+                    rent: ctx.accounts.rent.to_account_info(),
+                    system_program: ctx.accounts.system_program.to_account_info(),
+                },
+                &[&[wormhole::SEED_PREFIX_EMITTER, &[ctx.bumps.emitter]]],
+            ),
+            0,
+            message.try_to_vec()?,
+            ctx.accounts.config.wormhole_config.finality(),
+        )?;
+
+        emit!(BridgedOut {
+            sender: ctx.accounts.owner.key(),
+            amount,
+            recipient_chain,
+            recipient,
+            sequence,
+            fee,
+        });
+
+        Ok(())
+    }
+
+    #[access_control(authorized(
+        &ctx.accounts.config,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts
+    ))]
+    pub fn pause(ctx: Context<UpdateConfig>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        require!(!config.paused, TokenError::Paused);
+        config.paused = true;
+
+        emit!(Paused {
+            authority: ctx.accounts.authority.key(),
+        });
+
+        Ok(())
+    }
+
+    #[access_control(authorized(
+        &ctx.accounts.config,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts
+    ))]
+    pub fn unpause(ctx: Context<UpdateConfig>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        require!(config.paused, TokenError::NotPaused);
+        config.paused = false;
+
+        emit!(Unpaused {
+            authority: ctx.accounts.authority.key(),
+        });
+
+        Ok(())
+    }
+
+    #[access_control(authorized(
+        &ctx.accounts.config,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts
+    ))]
+    pub fn set_treasury_wallet(ctx: Context<SetTreasuryWallet>) -> Result<()> {
+        ctx.accounts.config.treasury_wallet = ctx.accounts.new_treasury_wallet.key();
+
+        emit!(ConfigUpdated {
+            parameter: ConfigParameter::TreasuryWallet,
+            authority: ctx.accounts.authority.key(),
+            value: ParameterValue::TreasuryWallet(ctx.accounts.config.treasury_wallet),
+        });
+
+        Ok(())
+    }
+
+    #[access_control(authorized(
+        &ctx.accounts.config,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts
+    ))]
+    pub fn set_min_stake_amount(ctx: Context<UpdateConfig>, new_amount: u64) -> Result<()> {
+        require!(new_amount > 0, TokenError::ZeroAmount);
+        ctx.accounts.config.min_stake_amount = new_amount;
+
+        emit!(ConfigUpdated {
+            parameter: ConfigParameter::MinStakeAmount,
+            authority: ctx.accounts.authority.key(),
+            value: ParameterValue::MinStakeAmount(new_amount),
+        });
+
+        Ok(())
+    }
+
+    #[access_control(authorized(
+        &ctx.accounts.config,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts
+    ))]
+    pub fn set_wormhole_config(
+        ctx: Context<UpdateConfig>,
+        wormhole_config: WormholeConfig,
+    ) -> Result<()> {
+        require!(
+            wormhole_config.bridge != Pubkey::default(),
+            TokenError::InvalidWormholeConfig
+        );
+        // The consistency level only changes through the timelock
+        require!(
+            wormhole_config.consistency_level
+                == ctx.accounts.config.wormhole_config.consistency_level,
+            TokenError::TimelockRequired
+        );
+        ctx.accounts.config.wormhole_config = wormhole_config.clone();
+
+        emit!(ConfigUpdated {
+            parameter: ConfigParameter::WormholeConfig,
+            authority: ctx.accounts.authority.key(),
+            value: ParameterValue::WormholeConfig(wormhole_config),
+        });
+
+        Ok(())
+    }
+
+    #[access_control(authorized(
+        &ctx.accounts.config,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts
+    ))]
+    pub fn schedule_change(ctx: Context<ScheduleChange>, change: ParameterChange) -> Result<()> {
+        change.validate()?;
+
+        let eta = Clock::get()?.unix_timestamp + TIMELOCK_DELAY;
+        let id = ctx.accounts.config.next_change_id;
+        ctx.accounts.config.next_change_id += 1;
+
+        let pending_change = &mut ctx.accounts.pending_change;
+        pending_change.id = id;
+        pending_change.change = change;
+        pending_change.eta = eta;
+        pending_change.scheduled_by = ctx.accounts.authority.key();
+        pending_change.bump = ctx.bumps.pending_change;
+
+        emit!(ChangeScheduled { id, change, eta });
+
+        Ok(())
+    }
+
+    pub fn execute_change(ctx: Context<ExecuteChange>) -> Result<()> {
+        let pending_change = &ctx.accounts.pending_change;
+        require!(
+            Clock::get()?.unix_timestamp >= pending_change.eta,
+            TokenError::TimelockNotExpired
+        );
+
+        let parameter = match pending_change.change {
+            ParameterChange::TransferTaxRate(new_rate) => {
+                // Token-2022 applies a new transfer fee two epochs after it is set
+                transfer_fee::transfer_fee_set(
+                    CpiContext::new_with_signer(
+                        ctx.accounts.token_program.to_account_info(),
+                        TransferFeeSetTransferFee {
+                            token_program_id: ctx.accounts.token_program.to_account_info(),
+                            mint: ctx.accounts.mint.to_account_info(),
+                            authority: ctx.accounts.config.to_account_info(),
+                        },
+                        &[&[CONFIG_SEED, &[ctx.accounts.config.bump]]],
+                    ),
+                    (new_rate * 100) as u16,
+                    u64::MAX,
+                )?;
+                ctx.accounts.config.transfer_tax_rate = new_rate;
+                ctx.accounts.config.transfer_tax_rate_epoch = Clock::get()?.epoch + 2;
+                ConfigParameter::TransferTaxRate
+            }
+            ParameterChange::EmissionRate(new_rate) => {
+                // Accrue at the old rate up to now before switching
+                let staking_pool = &mut ctx.accounts.staking_pool;
+                staking_pool.update(Clock::get()?.unix_timestamp);
+                staking_pool.emission_rate = new_rate;
+                ConfigParameter::EmissionRate
+            }
+            ParameterChange::ConsistencyLevel(level) => {
+                ctx.accounts.config.wormhole_config.consistency_level = level;
+                ConfigParameter::ConsistencyLevel
+            }
+            ParameterChange::BridgeFee(bps) => {
+                ctx.accounts.config.bridge_fee_bps = bps;
+                ConfigParameter::BridgeFee
+            }
+        };
+
+        emit!(ChangeExecuted {
+            id: pending_change.id,
+        });
+        emit!(ConfigUpdated {
+            parameter,
+            authority: pending_change.scheduled_by,
+            value: pending_change.change.into(),
+        });
+
+        Ok(())
+    }
+
+    #[access_control(authorized(
+        &ctx.accounts.config,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts
+    ))]
+    pub fn cancel_change(ctx: Context<CancelChange>) -> Result<()> {
+        emit!(ChangeCancelled {
+            id: ctx.accounts.pending_change.id,
+        });
+
+        Ok(())
+    }
+
+    #[access_control(authorized(
+        &ctx.accounts.config,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts
+    ))]
+    pub fn propose_authority(ctx: Context<UpdateConfig>, new_authority: Pubkey) -> Result<()> {
+        require!(
+            new_authority != Pubkey::default(),
+            TokenError::InvalidAuthority
+        );
+        ctx.accounts.config.pending_authority = new_authority;
+
+        emit!(AuthorityProposed {
+            authority: ctx.accounts.config.authority,
+            pending_authority: new_authority,
+        });
+
+        Ok(())
+    }
+
+    pub fn accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let previous_authority = config.authority;
+        config.authority = ctx.accounts.pending_authority.key();
+        config.pending_authority = Pubkey::default();
+        // The signer set belonged to the previous owner; the new authority
+        // starts as a single key and can install its own set
+        config.authority_signers = Vec::new();
+        config.authority_threshold = 0;
+
+        emit!(AuthorityTransferred {
+            previous_authority,
+            new_authority: config.authority,
+        });
+
+        Ok(())
+    }
+
+    #[access_control(authorized(
+        &ctx.accounts.config,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts
+    ))]
+    pub fn set_authority_signers(
+        ctx: Context<UpdateConfig>,
+        signers: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Result<()> {
+        require!(
+            signers.len() <= MAX_AUTHORITY_SIGNERS,
+            TokenError::TooManySigners
+        );
+        require!(
+            (threshold as usize) <= signers.len() && (threshold > 0 || signers.is_empty()),
+            TokenError::InvalidThreshold
+        );
+        for (i, signer) in signers.iter().enumerate() {
+            require!(
+                *signer != Pubkey::default() && !signers[..i].contains(signer),
+                TokenError::InvalidAuthority
+            );
+        }
+
+        let config = &mut ctx.accounts.config;
+        config.authority_signers = signers.clone();
+        config.authority_threshold = threshold;
+
+        emit!(AuthoritySignersUpdated { signers, threshold });
+
+        Ok(())
+    }
+
+    #[access_control(authorized(
+        &ctx.accounts.config,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts
+    ))]
+    pub fn set_lock_tiers(
+        ctx: Context<SetLockTiers>,
+        tiers: Vec<LockTier>,
+        early_exit_penalty_bps: u16,
+    ) -> Result<()> {
+        LockTiers::validate(&tiers, early_exit_penalty_bps)?;
+
+        // Existing positions keep the duration and multiplier they staked with
+        let lock_tiers = &mut ctx.accounts.lock_tiers;
+        lock_tiers.tiers = tiers.clone();
+        lock_tiers.early_exit_penalty_bps = early_exit_penalty_bps;
+
+        emit!(ConfigUpdated {
+            parameter: ConfigParameter::LockTiers,
+            authority: ctx.accounts.authority.key(),
+            value: ParameterValue::LockTiers {
+                tiers,
+                early_exit_penalty_bps,
+            },
+        });
+
+        Ok(())
+    }
+
+    /// Exempts `owner` from the transfer limit, like `setExemptFromLimit` on
+    /// EVM. The config PDA is the hook authority and signs the update.
+    #[access_control(authorized(
+        &ctx.accounts.config,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts
+    ))]
+    pub fn set_exemption(ctx: Context<SetExemption>, owner: Pubkey, exempt: bool) -> Result<()> {
+        capy_solana_transfer_hook::cpi::set_exemption(
+            CpiContext::new_with_signer(
+                ctx.accounts.transfer_hook_program.to_account_info(),
+                capy_solana_transfer_hook::cpi::accounts::SetExemption {
+                    authority: ctx.accounts.config.to_account_info(),
+                    payer: ctx.accounts.payer.to_account_info(),
+                    hook_config: ctx.accounts.hook_config.to_account_info(),
+                    exemption: ctx.accounts.exemption.to_account_info(),
+                    system_program: ctx.accounts.system_program.to_account_info(),
+                },
+                &[&[CONFIG_SEED, &[ctx.accounts.config.bump]]],
+            ),
+            owner,
+            exempt,
+        )?;
+
+        emit!(ConfigUpdated {
+            parameter: ConfigParameter::Exemption,
+            authority: ctx.accounts.authority.key(),
+            value: ParameterValue::Exemption { owner, exempt },
+        });
+
+        Ok(())
+    }
+
+    #[access_control(authorized(
+        &ctx.accounts.config,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts
+    ))]
+    pub fn set_max_transfer_amount(ctx: Context<SetMaxTransferAmount>, amount: u64) -> Result<()> {
+        capy_solana_transfer_hook::cpi::set_max_transfer_amount(
+            CpiContext::new_with_signer(
+                ctx.accounts.transfer_hook_program.to_account_info(),
+                capy_solana_transfer_hook::cpi::accounts::UpdateHookConfig {
+                    authority: ctx.accounts.config.to_account_info(),
+                    hook_config: ctx.accounts.hook_config.to_account_info(),
+                },
+                &[&[CONFIG_SEED, &[ctx.accounts.config.bump]]],
+            ),
+            amount,
+        )?;
+
+        emit!(ConfigUpdated {
+            parameter: ConfigParameter::MaxTransferAmount,
+            authority: ctx.accounts.authority.key(),
+            value: ParameterValue::MaxTransferAmount(amount),
+        });
+
+        Ok(())
+    }
+
+    #[access_control(authorized(
+        &ctx.accounts.config,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts
+    ))]
+    pub fn register_emitter(
+        ctx: Context<RegisterEmitter>,
+        chain: u16,
+        address: [u8; 32],
+        manager: [u8; 32],
+        token_decimals: u8,
+    ) -> Result<()> {
+        require!(
+            chain != 0 && chain != wormhole::CHAIN_ID_SOLANA,
+            TokenError::InvalidEmitterChain
+        );
+        require!(address != [0u8; 32], TokenError::InvalidEmitterAddress);
+        require!(manager != [0u8; 32], TokenError::InvalidPeerManager);
+
+        // The emitter is the peer's Wormhole transceiver; NTT messages also
+        // name the peer's manager, which must match on the way in
+        let foreign_emitter = &mut ctx.accounts.foreign_emitter;
+        if foreign_emitter.chain == 0 {
+            // New peers start at the default limits with full capacity
+            let now = Clock::get()?.unix_timestamp;
+            let limit = to_base_units(DEFAULT_RATE_LIMIT);
+            foreign_emitter.outbound = RateLimitState::new(limit, now);
+            foreign_emitter.inbound = RateLimitState::new(limit, now);
+        }
+        foreign_emitter.chain = chain;
+        foreign_emitter.address = address;
+        foreign_emitter.manager = manager;
+        foreign_emitter.token_decimals = token_decimals;
+
+        emit!(PeerRegistered {
+            chain,
+            address,
+            manager,
+            token_decimals,
+        });
+
+        Ok(())
+    }
+
+    pub fn bridge_in(ctx: Context<BridgeIn>, vaa_hash: [u8; 32]) -> Result<()> {
+        require!(!ctx.accounts.config.paused, TokenError::Paused);
+
+        // The posted VAA account only exists once the core bridge has
+        // verified the guardian signatures. The emitter and the recipient
+        // token account owner are checked by the account constraints.
+        let posted_vaa = &ctx.accounts.posted_vaa;
+        let message = posted_vaa.data();
+        require!(
+            message.recipient_chain == wormhole::CHAIN_ID_SOLANA,
+            TokenError::WrongTargetChain
+        );
+        require!(
+            message.source_manager == ctx.accounts.foreign_emitter.manager,
+            TokenError::UnknownPeerManager
+        );
+        require!(
+            message.recipient_manager == crate::ID.to_bytes(),
+            TokenError::WrongRecipientManager
+        );
+        // Amounts arrive in at most 8 decimals and are scaled to the mint's
+        require!(
+            message.amount.decimals <= TRIMMED_DECIMALS,
+            TokenError::InvalidAmountDecimals
+        );
+        let amount = message
+            .amount
+            .untrim(DECIMALS)
+            .ok_or(TokenError::AmountOverflow)?;
+
+        // Record the redemption so the same VAA cannot be minted twice
+        let claim = &mut ctx.accounts.claim;
+        require!(
+            !claim.redeemed && !claim.is_queued(),
+            TokenError::AlreadyRedeemed
+        );
+        claim.emitter_chain = posted_vaa.emitter_chain();
+        claim.emitter_address = *posted_vaa.emitter_address();
+        claim.sequence = posted_vaa.sequence();
+        claim.recipient = ctx.accounts.recipient.key();
+        claim.amount = amount;
+
+        // Transfers above the remaining capacity wait in the claim until the
+        // queue delay passes or the authority releases them
+        let now = Clock::get()?.unix_timestamp;
+        let foreign_emitter = &mut ctx.accounts.foreign_emitter;
+        if !foreign_emitter.inbound.consume(amount, now) {
+            claim.release_time = now + INBOUND_QUEUE_DELAY;
+
+            emit!(InboundQueued {
+                recipient: claim.recipient,
+                amount,
+                emitter_chain: claim.emitter_chain,
+                sequence: claim.sequence,
+                release_time: claim.release_time,
+            });
+
+            return Ok(());
+        }
+        foreign_emitter.outbound.refill(amount, now);
+        claim.redeemed = true;
+
+        // Mint tokens to recipient
+        mint_tokens(
+            &ctx.accounts.token_program,
+            &ctx.accounts.mint,
+            &ctx.accounts.mint_authority,
+            ctx.accounts.recipient.to_account_info(),
+            ctx.accounts.config.mint_authority_bump,
+            amount,
+        )?;
+
+        emit!(BridgedIn {
+            recipient: ctx.accounts.recipient.key(),
+            amount,
+            emitter_chain: claim.emitter_chain,
+            emitter_address: claim.emitter_address,
+            sequence: claim.sequence,
+            vaa_hash,
+        });
+
+        Ok(())
+    }
+
+    pub fn release_inbound_transfer(ctx: Context<ReleaseInboundTransfer>) -> Result<()> {
+        require!(!ctx.accounts.config.paused, TokenError::Paused);
+        require!(ctx.accounts.claim.is_queued(), TokenError::NotQueued);
+
+        // Anyone can release once the delay has passed; before that only the
+        // authority can
+        if Clock::get()?.unix_timestamp < ctx.accounts.claim.release_time {
+            authorized(
+                &ctx.accounts.config,
+                &ctx.accounts.caller,
+                ctx.remaining_accounts,
             )?;
+        }
+
+        let claim = &mut ctx.accounts.claim;
+        claim.redeemed = true;
+
+        mint_tokens(
+            &ctx.accounts.token_program,
+            &ctx.accounts.mint,
+            &ctx.accounts.mint_authority,
+            ctx.accounts.recipient.to_account_info(),
+            ctx.accounts.config.mint_authority_bump,
+            claim.amount,
+        )?;
+
+        emit!(InboundReleased {
+            recipient: claim.recipient,
+            amount: claim.amount,
+            emitter_chain: claim.emitter_chain,
+            sequence: claim.sequence,
+            released_by: ctx.accounts.caller.key(),
+        });
+
+        Ok(())
+    }
+
+    #[access_control(authorized(
+        &ctx.accounts.config,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts
+    ))]
+    pub fn set_rate_limits(
+        ctx: Context<SetRateLimits>,
+        chain: u16,
+        outbound_limit: u64,
+        inbound_limit: u64,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let foreign_emitter = &mut ctx.accounts.foreign_emitter;
+        foreign_emitter.outbound.set_limit(outbound_limit, now);
+        foreign_emitter.inbound.set_limit(inbound_limit, now);
+
+        emit!(RateLimitsUpdated {
+            chain,
+            outbound_limit,
+            inbound_limit,
+        });
+
+        Ok(())
+    }
+
+    pub fn refund_transfer(ctx: Context<RefundTransfer>, vaa_hash: [u8; 32]) -> Result<()> {
+        require!(!ctx.accounts.config.paused, TokenError::Paused);
+
+        // The rejection must come from the peer the transfer was sent to,
+        // which the account constraints check for the emitter, and name the
+        // transfer's message id
+        let rejection = ctx.accounts.posted_vaa.data();
+        require!(
+            rejection.source_manager == ctx.accounts.foreign_emitter.manager,
+            TokenError::UnknownPeerManager
+        );
+        require!(
+            rejection.recipient_manager == crate::ID.to_bytes(),
+            TokenError::WrongRecipientManager
+        );
+        let outbound_transfer = &mut ctx.accounts.outbound_transfer;
+        require!(
+            rejection.id == BridgeMessage::id_from_sequence(outbound_transfer.sequence),
+            TokenError::RejectionMismatch
+        );
+        let reason = rejection.reason;
+
+        require!(!outbound_transfer.refunded, TokenError::AlreadyRefunded);
+        outbound_transfer.refunded = true;
+
+        // The tokens never arrived, so the outbound capacity they used is
+        // given back
+        let now = Clock::get()?.unix_timestamp;
+        ctx.accounts
+            .foreign_emitter
+            .outbound
+            .refill(outbound_transfer.amount, now);
+
+        mint_tokens(
+            &ctx.accounts.token_program,
+            &ctx.accounts.mint,
+            &ctx.accounts.mint_authority,
+            ctx.accounts.sender_token.to_account_info(),
+            ctx.accounts.config.mint_authority_bump,
+            outbound_transfer.amount,
+        )?;
+
+        emit!(TransferRefunded {
+            sender: outbound_transfer.sender,
+            amount: outbound_transfer.amount,
+            recipient_chain: outbound_transfer.recipient_chain,
+            sequence: outbound_transfer.sequence,
+            reason,
+            vaa_hash,
+        });
+
+        Ok(())
+    }
+
+    /// Remaining capacity towards and from `chain`, returned for simulation.
+    pub fn remaining_capacity(
+        ctx: Context<RemainingCapacity>,
+        chain: u16,
+    ) -> Result<RateLimitCapacity> {
+        let now = Clock::get()?.unix_timestamp;
+        let foreign_emitter = &ctx.accounts.foreign_emitter;
+        Ok(RateLimitCapacity {
+            chain,
+            outbound: foreign_emitter.outbound.capacity_at(now),
+            inbound: foreign_emitter.inbound.capacity_at(now),
+        })
+    }
+
+    pub fn claim_team_tokens<'info>(
+        ctx: Context<'_, '_, '_, 'info, ClaimVesting<'info>>,
+    ) -> Result<()> {
+        require_keys_eq!(
+            ctx.accounts.beneficiary_token.key(),
+            ctx.accounts.config.team_wallet,
+            VestingError::WrongBeneficiary
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        let vested = ctx.accounts.user_vesting.vesting_info.linear_vested(now)?;
+        release_vested(ctx.accounts, ctx.remaining_accounts, vested)
+    }
+
+    pub fn claim_development_tokens<'info>(
+        ctx: Context<'_, '_, '_, 'info, ClaimVesting<'info>>,
+    ) -> Result<()> {
+        require_keys_eq!(
+            ctx.accounts.beneficiary_token.key(),
+            ctx.accounts.config.development_wallet,
+            VestingError::WrongBeneficiary
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        let vested = ctx.accounts.user_vesting.vesting_info.linear_vested(now)?;
+        release_vested(ctx.accounts, ctx.remaining_accounts, vested)
+    }
+
+    pub fn claim_marketing_tokens<'info>(
+        ctx: Context<'_, '_, '_, 'info, ClaimVesting<'info>>,
+    ) -> Result<()> {
+        require_keys_eq!(
+            ctx.accounts.beneficiary_token.key(),
+            ctx.accounts.config.marketing_wallet,
+            VestingError::WrongBeneficiary
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        let vested = ctx
+            .accounts
+            .user_vesting
+            .vesting_info
+            .quarterly_vested(now, MARKETING_VESTING_PERIOD)?;
+        release_vested(ctx.accounts, ctx.remaining_accounts, vested)
+    }
+}
+
+#[account]
+pub struct TokenConfig {
+    pub mint: Pubkey,
+    pub authority: Pubkey,
+    pub treasury_wallet: Pubkey,
+    pub development_wallet: Pubkey,
+    pub marketing_wallet: Pubkey,
+    pub team_wallet: Pubkey,
+    pub stake_vault: Pubkey,
+    pub reward_vault: Pubkey,
+    pub team_vesting_start: i64,
+    pub development_vesting_start: i64,
+    pub marketing_vesting_start: i64,
+    pub paused: bool,
+    pub wormhole_config: WormholeConfig,
+    pub transfer_tax_rate: u64,
+    /// Epoch from which Token-2022 charges `transfer_tax_rate`
+    pub transfer_tax_rate_epoch: u64,
+    pub min_stake_amount: u64,
+    pub bridge_fee_bps: u16,
+    pub pending_authority: Pubkey,
+    pub authority_signers: Vec<Pubkey>,
+    pub authority_threshold: u8,
+    pub next_change_id: u64,
+    pub bump: u8,
+    pub mint_authority_bump: u8,
+    pub vault_authority_bump: u8,
+}
+
+impl TokenConfig {
+    pub const LEN: usize = 32 * 8
+        + 8 * 3
+        + 1
+        + WormholeConfig::LEN
+        + 8 * 3
+        + 2
+        + 32
+        + 4
+        + 32 * MAX_AUTHORITY_SIGNERS
+        + 1
+        + 8
+        + 1
+        + 1
+        + 1;
+
+    pub fn is_multisig(&self) -> bool {
+        self.authority_threshold > 0
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, PartialEq, Eq, Debug)]
+pub struct WormholeConfig {
+    /// Core bridge config account that `bridge_out` posts through
+    pub bridge: Pubkey,
+    pub message_fee: u64,
+    pub consistency_level: u8,
+}
+
+#[account]
+pub struct PendingChange {
+    pub id: u64,
+    pub change: ParameterChange,
+    pub eta: i64,
+    pub scheduled_by: Pubkey,
+    pub bump: u8,
+}
+
+impl PendingChange {
+    pub const LEN: usize = 8 + ParameterChange::LEN + 8 + 32 + 1;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ParameterChange {
+    TransferTaxRate(u64),
+    EmissionRate(u64),
+    ConsistencyLevel(u8),
+    BridgeFee(u16),
+}
+
+impl ParameterChange {
+    pub const LEN: usize = 1 + 8;
+
+    pub fn validate(&self) -> Result<()> {
+        match *self {
+            ParameterChange::TransferTaxRate(rate) => {
+                require!(rate <= MAX_TRANSFER_TAX_RATE, TokenError::TaxRateTooHigh)
+            }
+            ParameterChange::EmissionRate(rate) => {
+                require!(rate <= MAX_EMISSION_RATE, StakeError::EmissionRateTooHigh)
+            }
+            // Wormhole on Solana: 0 = confirmed, 1 = finalized
+            ParameterChange::ConsistencyLevel(level) => {
+                require!(level <= 1, TokenError::InvalidWormholeConfig)
+            }
+            ParameterChange::BridgeFee(bps) => {
+                require!(bps <= MAX_BRIDGE_FEE_BPS, TokenError::BridgeFeeTooHigh)
+            }
+        }
+        Ok(())
+    }
+}
+
+impl WormholeConfig {
+    pub const LEN: usize = 32 + 8 + 1;
+
+    pub fn finality(&self) -> wormhole::Finality {
+        if self.consistency_level == 0 {
+            wormhole::Finality::Confirmed
+        } else {
+            wormhole::Finality::Finalized
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct StakeInfo {
+    pub amount: u64,
+    pub start_time: i64,
+    pub last_claim_time: i64,
+    pub lock_duration: i64,
+    pub multiplier_bps: u16,
+    pub reward_per_token_paid: u128,
+    pub pending_rewards: u64,
+}
+
+impl StakeInfo {
+    pub const LEN: usize = 8 + 8 + 8 + 8 + 2 + 16 + 8;
+
+    /// Share of the emission: the staked amount boosted by the lock-up
+    /// multiplier.
+    pub fn weight(&self) -> u128 {
+        self.amount as u128 * self.multiplier_bps as u128 / BPS_DENOMINATOR as u128
+    }
+}
+
+#[account]
+pub struct StakingPool {
+    pub reward_per_token: u128,
+    pub total_weight: u128,
+    pub emission_rate: u64,
+    pub emitted: u64,
+    pub emission_cap: u64,
+    pub last_update_time: i64,
+    pub bump: u8,
+}
+
+impl StakingPool {
+    pub const LEN: usize = 16 + 16 + 8 + 8 + 8 + 8 + 1;
+
+    /// Accrues the emission since the last update into `reward_per_token`.
+    /// Nothing is emitted while the pool is empty, and `emitted` never
+    /// exceeds `emission_cap`.
+    pub fn update(&mut self, now: i64) {
+        let elapsed = (now - self.last_update_time).max(0) as u128;
+        self.last_update_time = self.last_update_time.max(now);
+        if self.total_weight == 0 {
+            return;
+        }
+
+        let remaining = (self.emission_cap - self.emitted) as u128;
+        let emission = (self.emission_rate as u128 * elapsed).min(remaining);
+        // Rounds down, so the sum of all payouts stays within `emitted`
+        self.reward_per_token += emission * REWARD_PRECISION / self.total_weight;
+        self.emitted += emission as u64;
+    }
+
+    /// Rewards a position has earned up to the last pool update.
+    pub fn earned(&self, stake_info: &StakeInfo) -> u64 {
+        let accrued = stake_info.weight()
+            * (self.reward_per_token - stake_info.reward_per_token_paid)
+            / REWARD_PRECISION;
+        stake_info.pending_rewards + accrued as u64
+    }
+
+    /// Moves everything earned so far into `pending_rewards`.
+    pub fn settle(&self, stake_info: &mut StakeInfo) {
+        stake_info.pending_rewards = self.earned(stake_info);
+        stake_info.reward_per_token_paid = self.reward_per_token;
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LockTier {
+    pub duration: i64,
+    pub multiplier_bps: u16,
+}
+
+impl LockTier {
+    pub const LEN: usize = 8 + 2;
+
+    pub const fn new(duration: i64, multiplier_bps: u16) -> Self {
+        Self {
+            duration,
+            multiplier_bps,
+        }
+    }
+}
+
+#[account]
+pub struct LockTiers {
+    pub tiers: Vec<LockTier>,
+    pub early_exit_penalty_bps: u16,
+    pub bump: u8,
+}
+
+impl LockTiers {
+    pub const LEN: usize = 4 + LockTier::LEN * MAX_LOCK_TIERS + 2 + 1;
+
+    pub fn validate(tiers: &[LockTier], early_exit_penalty_bps: u16) -> Result<()> {
+        require!(
+            !tiers.is_empty() && tiers.len() <= MAX_LOCK_TIERS,
+            StakeError::InvalidTier
+        );
+        require!(
+            early_exit_penalty_bps <= MAX_EARLY_EXIT_PENALTY_BPS,
+            StakeError::PenaltyTooHigh
+        );
+        for (i, tier) in tiers.iter().enumerate() {
+            require!(
+                tier.duration >= 0 && (i == 0 || tier.duration > tiers[i - 1].duration),
+                StakeError::InvalidTier
+            );
+            require!(
+                tier.multiplier_bps as u64 >= BPS_DENOMINATOR
+                    && tier.multiplier_bps <= MAX_LOCK_MULTIPLIER_BPS,
+                StakeError::InvalidMultiplier
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct VestingInfo {
+    pub total_amount: u64,
+    pub claimed_amount: u64,
+    pub start_time: i64,
+    pub duration: i64,
+    pub cliff_period: Option<i64>,
+}
+
+#[account]
+pub struct UserStakeInfo {
+    pub owner: Pubkey,
+    pub index: u32,
+    pub stake_info: StakeInfo,
+}
+
+impl UserStakeInfo {
+    pub const LEN: usize = 32 + 4 + StakeInfo::LEN;
+}
+
+#[account]
+pub struct UserVestingInfo {
+    pub owner: Pubkey,
+    pub vesting_info: VestingInfo,
+}
+
+impl UserVestingInfo {
+    pub const LEN: usize = 32 + VestingInfo::LEN;
+}
+
+/// Redemption record of a VAA. Transfers above the inbound capacity stay
+/// queued here until `release_time`.
+#[account]
+pub struct VaaClaim {
+    pub emitter_chain: u16,
+    pub emitter_address: [u8; 32],
+    pub sequence: u64,
+    pub redeemed: bool,
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub release_time: i64,
+}
+
+impl VaaClaim {
+    pub const LEN: usize = 2 + 32 + 8 + 1 + 32 + 8 + 8;
+
+    pub fn is_queued(&self) -> bool {
+        !self.redeemed && self.release_time != 0
+    }
+}
+
+/// Outbound transfer, recorded by its Wormhole message account so the
+/// sender can be refunded if the destination chain rejects it.
+#[account]
+pub struct OutboundTransfer {
+    pub sender: Pubkey,
+    pub amount: u64,
+    pub recipient_chain: u16,
+    /// Wormhole sequence of the message, which makes up its NTT message id
+    pub sequence: u64,
+    pub message: Pubkey,
+    pub refunded: bool,
+}
+
+impl OutboundTransfer {
+    pub const LEN: usize = 32 + 8 + 2 + 8 + 32 + 1;
+}
+
+/// Registered NTT peer on a foreign chain and the rate limits of transfers
+/// to and from it.
+#[account]
+pub struct ForeignEmitter {
+    pub chain: u16,
+    pub address: [u8; 32],
+    pub manager: [u8; 32],
+    pub token_decimals: u8,
+    pub outbound: RateLimitState,
+    pub inbound: RateLimitState,
+}
+
+impl ForeignEmitter {
+    pub const LEN: usize = 2 + 32 + 32 + 1 + RateLimitState::LEN * 2;
+}
+
+/// Rolling-window rate limit: capacity refills linearly to `limit` over
+/// `RATE_LIMIT_DURATION`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct RateLimitState {
+    pub limit: u64,
+    pub capacity_at_last_tx: u64,
+    pub last_tx_timestamp: i64,
+}
+
+impl RateLimitState {
+    pub const LEN: usize = 8 + 8 + 8;
+
+    pub fn new(limit: u64, now: i64) -> Self {
+        Self {
+            limit,
+            capacity_at_last_tx: limit,
+            last_tx_timestamp: now,
+        }
+    }
+
+    pub fn capacity_at(&self, now: i64) -> u64 {
+        let elapsed = now.saturating_sub(self.last_tx_timestamp).max(0) as u128;
+        let refilled = self.limit as u128 * elapsed / RATE_LIMIT_DURATION as u128;
+        (self.capacity_at_last_tx as u128 + refilled).min(self.limit as u128) as u64
+    }
+
+    /// Takes `amount` out of the capacity; returns false and leaves the state
+    /// unchanged when it does not fit.
+    pub fn consume(&mut self, amount: u64, now: i64) -> bool {
+        let capacity = self.capacity_at(now);
+        if amount > capacity {
+            return false;
+        }
+        self.capacity_at_last_tx = capacity - amount;
+        self.last_tx_timestamp = now;
+        true
+    }
+
+    /// Returns `amount` to the capacity, up to the limit.
+    pub fn refill(&mut self, amount: u64, now: i64) {
+        self.capacity_at_last_tx = self.capacity_at(now).saturating_add(amount).min(self.limit);
+        self.last_tx_timestamp = now;
+    }
+
+    /// Moves the current capacity by the change in the limit.
+    pub fn set_limit(&mut self, limit: u64, now: i64) {
+        let capacity = self.capacity_at(now);
+        self.capacity_at_last_tx = if limit > self.limit {
+            capacity.saturating_add(limit - self.limit)
+        } else {
+            capacity.saturating_sub(self.limit - limit)
+        }
+        .min(limit);
+        self.limit = limit;
+        self.last_tx_timestamp = now;
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct RateLimitCapacity {
+    pub chain: u16,
+    pub outbound: u64,
+    pub inbound: u64,
+}
 
-            Ok(())
-        }
+/// Scales a whole-token amount to base units using the mint decimals.
+pub fn to_base_units(amount: u64) -> u64 {
+    amount * 10u64.pow(DECIMALS as u32)
+}
 
-        pub fn stake(ctx: Context<Stake>, amount: u64) -> Result<()> {
-            require!(amount >= 1000 * 1_000_000_000, StakeError::BelowMinimum); // 1000 tokens minimum
-            
-            let clock = Clock::get()?;
-            let stake_info = StakeInfo {
-                amount,
-                start_time: clock.unix_timestamp,
-                last_claim_time: clock.unix_timestamp,
-            };
+impl VestingInfo {
+    pub const LEN: usize = 8 + 8 + 8 + 8 + (1 + 8);
 
-            let user_stake = &mut ctx.accounts.user_stake;
-            user_stake.owner = ctx.accounts.owner.key();
-            user_stake.stake_info = stake_info;
+    /// Amount vested at `now` on a linear schedule. When a cliff is set, nothing
+    /// vests before it ends and the linear period starts from the cliff.
+    pub fn linear_vested(&self, now: i64) -> Result<u64> {
+        let cliff = self.cliff_period.unwrap_or(0);
+        let vesting_start = self.start_time + cliff;
+        require!(now >= vesting_start, VestingError::CliffNotReached);
 
-            token::transfer(
-                CpiContext::new(
-                    ctx.accounts.token_program.to_account_info(),
-                    token::Transfer {
-                        from: ctx.accounts.from.to_account_info(),
-                        to: ctx.accounts.stake_vault.to_account_info(),
-                        authority: ctx.accounts.owner.to_account_info(),
-                    },
-                ),
-                amount,
-            )?;
+        let elapsed = (now - vesting_start).min(self.duration);
+        let vested = (self.total_amount as u128 * elapsed as u128) / self.duration as u128;
+        Ok(vested as u64)
+    }
+
+    /// Amount vested at `now` when the allocation unlocks in equal tranches
+    /// every `period` seconds over `duration`.
+    pub fn quarterly_vested(&self, now: i64, period: i64) -> Result<u64> {
+        let total_periods = self.duration / period;
+        let periods = ((now - self.start_time).max(0) / period).min(total_periods);
+        let vested = (self.total_amount as u128 * periods as u128) / total_periods as u128;
+        Ok(vested as u64)
+    }
+}
 
-            Ok(())
+/// Checks that an admin instruction is approved by the authority. With an
+/// M-of-N signer set installed, `authority` and any signing
+/// `remaining_accounts` must together cover `authority_threshold` members.
+pub fn authorized(
+    config: &TokenConfig,
+    authority: &Signer,
+    cosigners: &[AccountInfo],
+) -> Result<()> {
+    if !config.is_multisig() {
+        require_keys_eq!(authority.key(), config.authority, TokenError::Unauthorized);
+        return Ok(());
+    }
+
+    let mut approvals: Vec<Pubkey> = Vec::with_capacity(config.authority_signers.len());
+    let signers = std::iter::once(authority.key())
+        .chain(cosigners.iter().filter(|a| a.is_signer).map(|a| a.key()));
+    for key in signers {
+        if config.authority_signers.contains(&key) && !approvals.contains(&key) {
+            approvals.push(key);
         }
+    }
+    require!(
+        approvals.len() >= config.authority_threshold as usize,
+        TokenError::NotEnoughSigners
+    );
 
-        pub fn unstake(ctx: Context<Unstake>) -> Result<()> {
-            let user_stake = &ctx.accounts.user_stake;
-            let amount = user_stake.stake_info.amount;
+    Ok(())
+}
 
-            // Claim rewards first
-            Self::claim_rewards(ctx.accounts)?;
+/// Bridge fee the treasury takes from an outbound transfer of `amount`.
+pub fn bridge_fee_for(config: &TokenConfig, amount: u64) -> u64 {
+    (amount as u128 * config.bridge_fee_bps as u128 / BPS_DENOMINATOR as u128) as u64
+}
 
-            token::transfer(
-                CpiContext::new(
-                    ctx.accounts.token_program.to_account_info(),
-                    token::Transfer {
-                        from: ctx.accounts.stake_vault.to_account_info(),
-                        to: ctx.accounts.owner_token.to_account_info(),
-                        authority: ctx.accounts.authority.to_account_info(),
-                    },
-                ),
-                amount,
-            )?;
+/// Sequence the core bridge assigns to the emitter's next message. The
+/// sequence account is only created by the emitter's first message, so until
+/// then it is empty and the next sequence is 0.
+pub fn next_sequence(sequence: &AccountInfo) -> Result<u64> {
+    let data = sequence.try_borrow_data()?;
+    if data.is_empty() {
+        return Ok(0);
+    }
+    Ok(wormhole::SequenceTracker::try_deserialize_unchecked(&mut &data[..])?.value())
+}
 
-            Ok(())
-        }
+/// Token-2022 `transfer_checked` that forwards the transfer hook's extra
+/// accounts, which callers pass as remaining accounts.
+#[allow(clippy::too_many_arguments)]
+fn transfer_with_hook<'info>(
+    token_program: &Program<'info, Token2022>,
+    from: AccountInfo<'info>,
+    mint: &InterfaceAccount<'info, Mint>,
+    to: AccountInfo<'info>,
+    authority: AccountInfo<'info>,
+    extra_accounts: &[AccountInfo<'info>],
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    spl_token_2022::onchain::invoke_transfer_checked(
+        token_program.key,
+        from,
+        mint.to_account_info(),
+        to,
+        authority,
+        extra_accounts,
+        amount,
+        DECIMALS,
+        signer_seeds,
+    )?;
+    Ok(())
+}
 
-        pub fn bridge_out(
-            ctx: Context<BridgeOut>,
-            amount: u64,
-            recipient_chain: u16,
-            recipient: [u8; 32],
-        ) -> Result<()> {
-            require!(!ctx.accounts.token.paused, TokenError::Paused);
-            require!(amount > 0, TokenError::ZeroAmount);
-
-            // Burn tokens
-            token::burn(
-                CpiContext::new(
-                    ctx.accounts.token_program.to_account_info(),
-                    token::Burn {
-                        mint: ctx.accounts.mint.to_account_info(),
-                        from: ctx.accounts.from.to_account_info(),
-                        authority: ctx.accounts.owner.to_account_info(),
-                    },
-                ),
-                amount,
-            )?;
+/// `transfer_with_hook` into or out of a program vault that leaves `to` with
+/// the full `amount`, as the EVM contract is exempt from the tax. Token-2022
+/// withholds the fee in `to` anyway, so the config PDA, which is the mint's
+/// withdraw authority, hands it straight back. Fees already withheld in `to`
+/// are harvested to the mint first and still go to the treasury.
+#[allow(clippy::too_many_arguments)]
+fn transfer_without_fee<'info>(
+    token_program: &Program<'info, Token2022>,
+    config: &Account<'info, TokenConfig>,
+    from: AccountInfo<'info>,
+    mint: &InterfaceAccount<'info, Mint>,
+    to: AccountInfo<'info>,
+    authority: AccountInfo<'info>,
+    extra_accounts: &[AccountInfo<'info>],
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    transfer_fee::harvest_withheld_tokens_to_mint(
+        CpiContext::new(
+            token_program.to_account_info(),
+            HarvestWithheldTokensToMint {
+                token_program_id: token_program.to_account_info(),
+                mint: mint.to_account_info(),
+            },
+        ),
+        vec![to.clone()],
+    )?;
 
-            // Post Wormhole message
-            let message = BridgeMessage {
-                amount,
-                token_address: ctx.accounts.mint.key(),
-                recipient_chain,
-                recipient,
-            };
+    transfer_with_hook(
+        token_program,
+        from,
+        mint,
+        to.clone(),
+        authority,
+        extra_accounts,
+        amount,
+        signer_seeds,
+    )?;
 
-            wormhole::post_message(
-                CpiContext::new(
-                    ctx.accounts.wormhole_program.to_account_info(),
-                    wormhole::PostMessage {
-                        config: ctx.accounts.config.to_account_info(),
-                        message: ctx.accounts.message.to_account_info(),
-                        emitter: ctx.accounts.emitter.to_account_info(),
-                        sequence: ctx.accounts.sequence.to_account_info(),
-                        payer: ctx.accounts.payer.to_account_info(),
-                        fee_collector: ctx.accounts.fee_collector.to_account_info(),
-                        clock: ctx.accounts.clock.to_account_info(),
-                        rent: ctx.accounts.rent.to_account_info(),
-                        system_program: ctx.accounts.system_program.to_account_info(),
-                    },
-                ),
-                ctx.accounts.token.wormhole_config.consistency_level,
-                message.try_to_vec()?,
-                ctx.accounts.token.wormhole_config.message_fee,
-            )?;
+    let withdraw = withdraw_withheld_tokens_from_accounts(
+        token_program.key,
+        &mint.key(),
+        to.key,
+        &config.key(),
+        &[],
+        &[to.key],
+    )?;
+    invoke_signed(
+        &withdraw,
+        &[mint.to_account_info(), to, config.to_account_info()],
+        &[&[CONFIG_SEED, &[config.bump]]],
+    )?;
+    Ok(())
+}
 
-            Ok(())
-        }
+fn mint_tokens<'info>(
+    token_program: &Program<'info, Token2022>,
+    mint: &InterfaceAccount<'info, Mint>,
+    mint_authority: &UncheckedAccount<'info>,
+    to: AccountInfo<'info>,
+    mint_authority_bump: u8,
+    amount: u64,
+) -> Result<()> {
+    token_interface::mint_to(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            token_interface::MintTo {
+                mint: mint.to_account_info(),
+                to,
+                authority: mint_authority.to_account_info(),
+            },
+            &[&[MINT_AUTHORITY_SEED, &[mint_authority_bump]]],
+        ),
+        amount,
+    )
+}
 
-        pub fn bridge_in(
-            ctx: Context<BridgeIn>,
-            vaa: Vec<u8>,
-        ) -> Result<()> {
-            require!(!ctx.accounts.token.paused, TokenError::Paused);
+#[allow(clippy::too_many_arguments)]
+fn pay_rewards<'info>(
+    token_program: &Program<'info, Token2022>,
+    config: &Account<'info, TokenConfig>,
+    reward_vault: &InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    to: &InterfaceAccount<'info, TokenAccount>,
+    vault_authority: &UncheckedAccount<'info>,
+    extra_accounts: &[AccountInfo<'info>],
+    amount: u64,
+) -> Result<()> {
+    require!(
+        reward_vault.amount >= amount,
+        StakeError::RewardPoolDepleted
+    );
 
-            // Verify and parse VAA
-            let parsed = wormhole::parse_vaa(&vaa)?;
-            let message: BridgeMessage = BridgeMessage::try_from_slice(&parsed.payload)?;
+    transfer_without_fee(
+        token_program,
+        config,
+        reward_vault.to_account_info(),
+        mint,
+        to.to_account_info(),
+        vault_authority.to_account_info(),
+        extra_accounts,
+        amount,
+        &[&[VAULT_AUTHORITY_SEED, &[config.vault_authority_bump]]],
+    )
+}
 
-            // Mint tokens to recipient
-            token::mint_to(
-                CpiContext::new(
-                    ctx.accounts.token_program.to_account_info(),
-                    token::MintTo {
-                        mint: ctx.accounts.mint.to_account_info(),
-                        to: ctx.accounts.recipient.to_account_info(),
-                        authority: ctx.accounts.authority.to_account_info(),
-                    },
-                ),
-                message.amount,
-            )?;
+fn release_vested<'info>(
+    accounts: &mut ClaimVesting<'info>,
+    extra_accounts: &[AccountInfo<'info>],
+    vested: u64,
+) -> Result<()> {
+    let vesting_info = &mut accounts.user_vesting.vesting_info;
+    let claimable = vested.saturating_sub(vesting_info.claimed_amount);
+    require!(claimable > 0, VestingError::NothingToClaim);
+    vesting_info.claimed_amount += claimable;
 
-            Ok(())
-        }
-    }
+    transfer_without_fee(
+        &accounts.token_program,
+        &accounts.config,
+        accounts.escrow.to_account_info(),
+        &accounts.mint,
+        accounts.beneficiary_token.to_account_info(),
+        accounts.vault_authority.to_account_info(),
+        extra_accounts,
+        claimable,
+        &[&[
+            VAULT_AUTHORITY_SEED,
+            &[accounts.config.vault_authority_bump],
+        ]],
+    )?;
+
+    emit!(VestingClaimed {
+        beneficiary: accounts.beneficiary_token.key(),
+        amount: claimable,
+        total_claimed: accounts.user_vesting.vesting_info.claimed_amount,
+    });
+
+    Ok(())
 }
 
 #[error_code]
@@ -254,6 +2012,70 @@ pub enum TokenError {
     ExceedsMaximum,
     #[msg("Insufficient balance")]
     InsufficientBalance,
+    #[msg("VAA has already been redeemed")]
+    AlreadyRedeemed,
+    #[msg("Signer is not the program authority")]
+    Unauthorized,
+    #[msg("Emitter chain is not a valid foreign chain")]
+    InvalidEmitterChain,
+    #[msg("Emitter address cannot be zero")]
+    InvalidEmitterAddress,
+    #[msg("VAA was not emitted by the registered CAPYAI emitter")]
+    UnknownEmitter,
+    #[msg("Bridge message is not addressed to Solana")]
+    WrongTargetChain,
+    #[msg("Recipient token account is not owned by the bridge message recipient")]
+    RecipientMismatch,
+    #[msg("Signer is not the program's upgrade authority")]
+    NotUpgradeAuthority,
+    #[msg("Minted supply does not match the initial supply")]
+    SupplyMismatch,
+    #[msg("Token transfers are not paused")]
+    NotPaused,
+    #[msg("Tax rate too high")]
+    TaxRateTooHigh,
+    #[msg("Wormhole bridge address cannot be zero")]
+    InvalidWormholeConfig,
+    #[msg("Wormhole bridge is not the configured core bridge")]
+    WrongWormholeBridge,
+    #[msg("Invalid authority")]
+    InvalidAuthority,
+    #[msg("Signer is not the pending authority")]
+    NotPendingAuthority,
+    #[msg("Too many authority signers")]
+    TooManySigners,
+    #[msg("Threshold must be between 1 and the number of signers")]
+    InvalidThreshold,
+    #[msg("Not enough authority signers approved")]
+    NotEnoughSigners,
+    #[msg("Change is still timelocked")]
+    TimelockNotExpired,
+    #[msg("This parameter can only be changed through the timelock")]
+    TimelockRequired,
+    #[msg("Peer manager address cannot be zero")]
+    InvalidPeerManager,
+    #[msg("Bridge message was not sent by the registered peer manager")]
+    UnknownPeerManager,
+    #[msg("Bridge message is not addressed to this manager")]
+    WrongRecipientManager,
+    #[msg("Bridge message amount uses unsupported decimals")]
+    InvalidAmountDecimals,
+    #[msg("Malformed NTT message")]
+    InvalidNttMessage,
+    #[msg("Transfer exceeds the remaining rate limit capacity")]
+    RateLimitExceeded,
+    #[msg("Transfer is not queued")]
+    NotQueued,
+    #[msg("Bridge fee too high")]
+    BridgeFeeTooHigh,
+    #[msg("Wormhole message fee exceeds the configured maximum")]
+    WormholeFeeTooHigh,
+    #[msg("Amount does not fit in a u64 after scaling")]
+    AmountOverflow,
+    #[msg("Rejection does not name this transfer")]
+    RejectionMismatch,
+    #[msg("Transfer has already been refunded")]
+    AlreadyRefunded,
 }
 
 #[error_code]
@@ -262,92 +2084,1128 @@ pub enum StakeError {
     BelowMinimum,
     #[msg("No rewards to claim")]
     NoRewards,
+    #[msg("Reward vault does not hold enough tokens")]
+    RewardPoolDepleted,
+    #[msg("Emission rate too high")]
+    EmissionRateTooHigh,
+    #[msg("Insufficient staked amount")]
+    InsufficientStake,
+    #[msg("Invalid lock-up tier")]
+    InvalidTier,
+    #[msg("Lock-up multiplier out of range")]
+    InvalidMultiplier,
+    #[msg("Early-exit penalty too high")]
+    PenaltyTooHigh,
+    #[msg("Top-up must use the position's lock-up tier")]
+    TierMismatch,
+    #[msg("Stake is still locked")]
+    LockActive,
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize)]
-pub struct BridgeMessage {
+#[error_code]
+pub enum VestingError {
+    #[msg("Cliff period not ended")]
+    CliffNotReached,
+    #[msg("No tokens to claim")]
+    NothingToClaim,
+    #[msg("Token account is not the vesting beneficiary")]
+    WrongBeneficiary,
+    #[msg("Signer does not own the beneficiary token account")]
+    Unauthorized,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ConfigParameter {
+    TransferTaxRate,
+    TreasuryWallet,
+    EmissionRate,
+    MinStakeAmount,
+    WormholeConfig,
+    Exemption,
+    MaxTransferAmount,
+    ConsistencyLevel,
+    LockTiers,
+    BridgeFee,
+}
+
+/// New value of the parameter named by `ConfigUpdated`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Debug)]
+pub enum ParameterValue {
+    TransferTaxRate(u64),
+    TreasuryWallet(Pubkey),
+    EmissionRate(u64),
+    MinStakeAmount(u64),
+    WormholeConfig(WormholeConfig),
+    Exemption {
+        owner: Pubkey,
+        exempt: bool,
+    },
+    MaxTransferAmount(u64),
+    ConsistencyLevel(u8),
+    LockTiers {
+        tiers: Vec<LockTier>,
+        early_exit_penalty_bps: u16,
+    },
+    BridgeFee(u16),
+}
+
+impl From<ParameterChange> for ParameterValue {
+    fn from(change: ParameterChange) -> Self {
+        match change {
+            ParameterChange::TransferTaxRate(rate) => Self::TransferTaxRate(rate),
+            ParameterChange::EmissionRate(rate) => Self::EmissionRate(rate),
+            ParameterChange::ConsistencyLevel(level) => Self::ConsistencyLevel(level),
+            ParameterChange::BridgeFee(bps) => Self::BridgeFee(bps),
+        }
+    }
+}
+
+// Event field order is decoded by the indexer; only append new fields
+
+#[event]
+pub struct Staked {
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub index: u32,
+    pub compounded: u64,
+}
+
+#[event]
+pub struct Unstaked {
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub rewards: u64,
+    pub index: u32,
+    pub penalty: u64,
+}
+
+#[event]
+pub struct RewardsClaimed {
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub index: u32,
+}
+
+#[event]
+pub struct BridgedOut {
+    pub sender: Pubkey,
+    pub amount: u64,
+    pub recipient_chain: u16,
+    pub recipient: [u8; 32],
+    pub sequence: u64,
+    pub fee: u64,
+}
+
+#[event]
+pub struct BridgedIn {
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub emitter_chain: u16,
+    pub emitter_address: [u8; 32],
+    pub sequence: u64,
+    pub vaa_hash: [u8; 32],
+}
+
+#[event]
+pub struct InboundQueued {
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub emitter_chain: u16,
+    pub sequence: u64,
+    pub release_time: i64,
+}
+
+#[event]
+pub struct InboundReleased {
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub emitter_chain: u16,
+    pub sequence: u64,
+    pub released_by: Pubkey,
+}
+
+#[event]
+pub struct RateLimitsUpdated {
+    pub chain: u16,
+    pub outbound_limit: u64,
+    pub inbound_limit: u64,
+}
+
+#[event]
+pub struct TransferRefunded {
+    pub sender: Pubkey,
     pub amount: u64,
-    pub token_address: Pubkey,
     pub recipient_chain: u16,
+    pub sequence: u64,
+    pub reason: u8,
+    pub vaa_hash: [u8; 32],
+}
+
+#[event]
+pub struct Paused {
+    pub authority: Pubkey,
+}
+
+#[event]
+pub struct Unpaused {
+    pub authority: Pubkey,
+}
+
+#[event]
+pub struct ConfigUpdated {
+    pub parameter: ConfigParameter,
+    pub authority: Pubkey,
+    pub value: ParameterValue,
+}
+
+#[event]
+pub struct ChangeScheduled {
+    pub id: u64,
+    pub change: ParameterChange,
+    pub eta: i64,
+}
+
+#[event]
+pub struct ChangeExecuted {
+    pub id: u64,
+}
+
+#[event]
+pub struct ChangeCancelled {
+    pub id: u64,
+}
+
+#[event]
+pub struct AuthorityProposed {
+    pub authority: Pubkey,
+    pub pending_authority: Pubkey,
+}
+
+#[event]
+pub struct AuthorityTransferred {
+    pub previous_authority: Pubkey,
+    pub new_authority: Pubkey,
+}
+
+#[event]
+pub struct AuthoritySignersUpdated {
+    pub signers: Vec<Pubkey>,
+    pub threshold: u8,
+}
+
+#[event]
+pub struct PeerRegistered {
+    pub chain: u16,
+    pub address: [u8; 32],
+    pub manager: [u8; 32],
+    pub token_decimals: u8,
+}
+
+#[event]
+pub struct VestingClaimed {
+    pub beneficiary: Pubkey,
+    pub amount: u64,
+    pub total_claimed: u64,
+}
+
+#[event]
+pub struct FeesCollected {
+    pub treasury_wallet: Pubkey,
+    pub amount: u64,
+}
+
+/// Amount as carried in NTT messages, together with its decimals.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TrimmedAmount {
+    pub amount: u64,
+    pub decimals: u8,
+}
+
+impl TrimmedAmount {
+    pub fn new(amount: u64, decimals: u8) -> Self {
+        Self { amount, decimals }
+    }
+
+    /// Trims `amount`, in `from_decimals`, to the decimals a peer with
+    /// `to_decimals` can represent, capped at `TRIMMED_DECIMALS`. Digits
+    /// below that are dropped.
+    pub fn trim(amount: u64, from_decimals: u8, to_decimals: u8) -> Self {
+        let decimals = TRIMMED_DECIMALS.min(from_decimals).min(to_decimals);
+        // Scaling down never overflows
+        Self::new(
+            scale_amount(amount, from_decimals, decimals).unwrap_or(0),
+            decimals,
+        )
+    }
+
+    /// The amount in `to_decimals`, or `None` if it does not fit in a u64.
+    pub fn untrim(&self, to_decimals: u8) -> Option<u64> {
+        scale_amount(self.amount, self.decimals, to_decimals)
+    }
+}
+
+/// Converts `amount` between decimals, truncating when scaling down.
+/// Returns `None` if the result does not fit in a u64.
+pub fn scale_amount(amount: u64, from_decimals: u8, to_decimals: u8) -> Option<u64> {
+    if from_decimals >= to_decimals {
+        let factor = 10u64.checked_pow((from_decimals - to_decimals) as u32);
+        Some(factor.map_or(0, |factor| amount / factor))
+    } else {
+        10u64
+            .checked_pow((to_decimals - from_decimals) as u32)?
+            .checked_mul(amount)
+    }
+}
+
+/// Wormhole NTT transceiver message wrapping a native token transfer in
+/// burn-and-mint mode. Encoded big-endian in the NTT wire format instead of
+/// Borsh so EVM NTT managers and standard relayers can read it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BridgeMessage {
+    pub source_manager: [u8; 32],
+    pub recipient_manager: [u8; 32],
+    pub id: [u8; 32],
+    pub sender: [u8; 32],
+    pub amount: TrimmedAmount,
+    pub source_token: [u8; 32],
     pub recipient: [u8; 32],
+    pub recipient_chain: u16,
+}
+
+impl BridgeMessage {
+    pub const TRANSFER_LEN: usize = 4 + 1 + 8 + 32 + 32 + 2;
+    pub const MANAGER_MESSAGE_LEN: usize = 32 + 32 + 2 + Self::TRANSFER_LEN;
+    pub const LEN: usize = 4 + 32 + 32 + 2 + Self::MANAGER_MESSAGE_LEN + 2;
+
+    /// Message id of an outbound transfer: the Wormhole sequence, left-padded.
+    pub fn id_from_sequence(sequence: u64) -> [u8; 32] {
+        let mut id = [0u8; 32];
+        id[24..].copy_from_slice(&sequence.to_be_bytes());
+        id
+    }
+}
+
+impl AnchorSerialize for BridgeMessage {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&NTT_TRANSCEIVER_PREFIX)?;
+        writer.write_all(&self.source_manager)?;
+        writer.write_all(&self.recipient_manager)?;
+        writer.write_all(&(Self::MANAGER_MESSAGE_LEN as u16).to_be_bytes())?;
+
+        writer.write_all(&self.id)?;
+        writer.write_all(&self.sender)?;
+        writer.write_all(&(Self::TRANSFER_LEN as u16).to_be_bytes())?;
+
+        writer.write_all(&NTT_TRANSFER_PREFIX)?;
+        writer.write_all(&[self.amount.decimals])?;
+        writer.write_all(&self.amount.amount.to_be_bytes())?;
+        writer.write_all(&self.source_token)?;
+        writer.write_all(&self.recipient)?;
+        writer.write_all(&self.recipient_chain.to_be_bytes())?;
+
+        // No transceiver-specific payload
+        writer.write_all(&0u16.to_be_bytes())
+    }
+}
+
+impl AnchorDeserialize for BridgeMessage {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        expect_prefix(reader, &NTT_TRANSCEIVER_PREFIX)?;
+        let source_manager = read_bytes(reader)?;
+        let recipient_manager = read_bytes(reader)?;
+
+        // Trailing bytes inside each length-prefixed section belong to
+        // newer payload versions and are skipped
+        let manager_message = read_section(reader)?;
+        let manager_message = &mut manager_message.as_slice();
+        let id = read_bytes(manager_message)?;
+        let sender = read_bytes(manager_message)?;
+        let transfer = read_section(manager_message)?;
+        let transfer = &mut transfer.as_slice();
+
+        expect_prefix(transfer, &NTT_TRANSFER_PREFIX)?;
+        let [decimals] = read_bytes(transfer)?;
+        let amount = u64::from_be_bytes(read_bytes(transfer)?);
+        let source_token = read_bytes(transfer)?;
+        let recipient = read_bytes(transfer)?;
+        let recipient_chain = u16::from_be_bytes(read_bytes(transfer)?);
+
+        read_section(reader)?;
+
+        Ok(Self {
+            source_manager,
+            recipient_manager,
+            id,
+            sender,
+            amount: TrimmedAmount::new(amount, decimals),
+            source_token,
+            recipient,
+            recipient_chain,
+        })
+    }
+}
+
+/// Message a peer sends back over Wormhole when it rejects a transfer from
+/// Solana, for example for an invalid recipient or while its token is
+/// paused. Encoded big-endian like `BridgeMessage`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransferRejected {
+    pub source_manager: [u8; 32],
+    pub recipient_manager: [u8; 32],
+    /// NTT message id of the rejected transfer
+    pub id: [u8; 32],
+    /// Rejection code defined by the peer; only reported in events
+    pub reason: u8,
+}
+
+impl TransferRejected {
+    pub const LEN: usize = 4 + 32 + 32 + 32 + 1;
+}
+
+impl AnchorSerialize for TransferRejected {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&TRANSFER_REJECTED_PREFIX)?;
+        writer.write_all(&self.source_manager)?;
+        writer.write_all(&self.recipient_manager)?;
+        writer.write_all(&self.id)?;
+        writer.write_all(&[self.reason])
+    }
+}
+
+impl AnchorDeserialize for TransferRejected {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        expect_prefix(reader, &TRANSFER_REJECTED_PREFIX)?;
+        let source_manager = read_bytes(reader)?;
+        let recipient_manager = read_bytes(reader)?;
+        let id = read_bytes(reader)?;
+        let [reason] = read_bytes(reader)?;
+        Ok(Self {
+            source_manager,
+            recipient_manager,
+            id,
+            reason,
+        })
+    }
+}
+
+fn read_bytes<R: Read, const N: usize>(reader: &mut R) -> io::Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Reads a section prefixed with its big-endian u16 length.
+fn read_section<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let len = u16::from_be_bytes(read_bytes(reader)?) as usize;
+    let mut section = vec![0u8; len];
+    reader.read_exact(&mut section)?;
+    Ok(section)
+}
+
+fn expect_prefix<R: Read>(reader: &mut R, prefix: &[u8; 4]) -> io::Result<()> {
+    if read_bytes::<R, 4>(reader)? != *prefix {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            TokenError::InvalidNttMessage.to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct InitializeMint<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// Only the upgrade authority may initialize a freshly deployed program
+    #[account(
+        seeds = [crate::ID.as_ref()],
+        bump,
+        seeds::program = bpf_loader_upgradeable::ID,
+        constraint = program_data.upgrade_authority_address == Some(authority.key())
+            @ TokenError::NotUpgradeAuthority
+    )]
+    pub program_data: Account<'info, ProgramData>,
+    /// CHECK: created and initialized as a Token-2022 mint by the instruction
+    #[account(mut, seeds = [MINT_SEED], bump)]
+    pub mint: UncheckedAccount<'info>,
+    /// CHECK: PDA that signs every mint of the token
+    #[account(seeds = [MINT_AUTHORITY_SEED], bump)]
+    pub mint_authority: UncheckedAccount<'info>,
+    /// CHECK: config PDA, created later by `initialize`; authority of the mint extensions
+    #[account(seeds = [CONFIG_SEED], bump)]
+    pub config: UncheckedAccount<'info>,
+    /// CHECK: created by the transfer hook program
+    #[account(mut)]
+    pub extra_account_meta_list: UncheckedAccount<'info>,
+    /// CHECK: created by the transfer hook program
+    #[account(mut)]
+    pub hook_config: UncheckedAccount<'info>,
+    pub transfer_hook_program: Program<'info, CapySolanaTransferHook>,
+    pub token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
 pub struct Initialize<'info> {
-    #[account(init, payer = authority, mint::decimals = 9, mint::authority = authority)]
-    pub mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [MINT_SEED],
+        bump,
+        mint::authority = mint_authority,
+        mint::token_program = token_program
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: PDA that signs every mint of the token
+    #[account(seeds = [MINT_AUTHORITY_SEED], bump)]
+    pub mint_authority: UncheckedAccount<'info>,
+    /// CHECK: PDA that owns the stake, reward and vesting escrow vaults
+    #[account(seeds = [VAULT_AUTHORITY_SEED], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
     #[account(mut)]
     pub authority: Signer<'info>,
-    pub treasury_wallet: Account<'info, TokenAccount>,
-    pub development_wallet: Account<'info, TokenAccount>,
-    pub marketing_wallet: Account<'info, TokenAccount>,
-    pub team_wallet: Account<'info, TokenAccount>,
-    pub token: Account<'info, CapySolanaToken>,
-    pub token_program: Program<'info, Token>,
+    #[account(
+        seeds = [crate::ID.as_ref()],
+        bump,
+        seeds::program = bpf_loader_upgradeable::ID,
+        constraint = program_data.upgrade_authority_address == Some(authority.key())
+            @ TokenError::NotUpgradeAuthority
+    )]
+    pub program_data: Account<'info, ProgramData>,
+    /// CHECK: wallet that owns the treasury token account
+    pub treasury_owner: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = authority,
+        associated_token::mint = mint,
+        associated_token::authority = treasury_owner,
+        associated_token::token_program = token_program
+    )]
+    pub treasury_wallet: Box<InterfaceAccount<'info, TokenAccount>>,
+    /// Token account that receives development vesting
+    #[account(token::mint = mint, token::token_program = token_program)]
+    pub development_wallet: Box<InterfaceAccount<'info, TokenAccount>>,
+    /// Token account that receives marketing vesting
+    #[account(token::mint = mint, token::token_program = token_program)]
+    pub marketing_wallet: Box<InterfaceAccount<'info, TokenAccount>>,
+    /// Token account that receives team vesting
+    #[account(token::mint = mint, token::token_program = token_program)]
+    pub team_wallet: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init,
+        payer = authority,
+        token::mint = mint,
+        token::authority = vault_authority,
+        token::token_program = token_program,
+        seeds = [STAKE_VAULT_SEED],
+        bump
+    )]
+    pub stake_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init,
+        payer = authority,
+        token::mint = mint,
+        token::authority = vault_authority,
+        token::token_program = token_program,
+        seeds = [REWARD_VAULT_SEED],
+        bump
+    )]
+    pub reward_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init,
+        payer = authority,
+        token::mint = mint,
+        token::authority = vault_authority,
+        token::token_program = token_program,
+        seeds = [VESTING_ESCROW_SEED, development_wallet.key().as_ref()],
+        bump
+    )]
+    pub development_escrow: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init,
+        payer = authority,
+        token::mint = mint,
+        token::authority = vault_authority,
+        token::token_program = token_program,
+        seeds = [VESTING_ESCROW_SEED, marketing_wallet.key().as_ref()],
+        bump
+    )]
+    pub marketing_escrow: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init,
+        payer = authority,
+        token::mint = mint,
+        token::authority = vault_authority,
+        token::token_program = token_program,
+        seeds = [VESTING_ESCROW_SEED, team_wallet.key().as_ref()],
+        bump
+    )]
+    pub team_escrow: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + TokenConfig::LEN,
+        seeds = [CONFIG_SEED],
+        bump
+    )]
+    pub config: Account<'info, TokenConfig>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + StakingPool::LEN,
+        seeds = [STAKING_POOL_SEED],
+        bump
+    )]
+    pub staking_pool: Box<Account<'info, StakingPool>>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + LockTiers::LEN,
+        seeds = [LOCK_TIERS_SEED],
+        bump
+    )]
+    pub lock_tiers: Box<Account<'info, LockTiers>>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + UserVestingInfo::LEN,
+        seeds = [VESTING_SEED, team_wallet.key().as_ref()],
+        bump
+    )]
+    pub team_vesting: Account<'info, UserVestingInfo>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + UserVestingInfo::LEN,
+        seeds = [VESTING_SEED, development_wallet.key().as_ref()],
+        bump
+    )]
+    pub development_vesting: Account<'info, UserVestingInfo>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + UserVestingInfo::LEN,
+        seeds = [VESTING_SEED, marketing_wallet.key().as_ref()],
+        bump
+    )]
+    pub marketing_vesting: Account<'info, UserVestingInfo>,
+    /// CHECK: checked by the transfer hook program
+    pub hook_config: UncheckedAccount<'info>,
+    /// CHECK: exemption of the vault authority, created by the transfer hook program
+    #[account(mut)]
+    pub vault_exemption: UncheckedAccount<'info>,
+    /// CHECK: exemption of the treasury owner, created by the transfer hook program
+    #[account(mut)]
+    pub treasury_exemption: UncheckedAccount<'info>,
+    pub transfer_hook_program: Program<'info, CapySolanaTransferHook>,
+    pub token_program: Program<'info, Token2022>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
+#[instruction(index: u32)]
 pub struct Stake<'info> {
     #[account(mut)]
     pub owner: Signer<'info>,
+    #[account(mut, token::mint = mint)]
+    pub from: InterfaceAccount<'info, TokenAccount>,
     #[account(mut)]
-    pub from: Account<'info, TokenAccount>,
+    pub stake_vault: InterfaceAccount<'info, TokenAccount>,
     #[account(mut)]
-    pub stake_vault: Account<'info, TokenAccount>,
-    #[account(init, payer = owner)]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: PDA that owns the stake, reward and vesting escrow vaults
+    #[account(seeds = [VAULT_AUTHORITY_SEED], bump = config.vault_authority_bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + UserStakeInfo::LEN,
+        seeds = [STAKE_SEED, owner.key().as_ref(), &index.to_le_bytes()],
+        bump
+    )]
     pub user_stake: Account<'info, UserStakeInfo>,
-    pub token_program: Program<'info, Token>,
+    #[account(seeds = [LOCK_TIERS_SEED], bump = lock_tiers.bump)]
+    pub lock_tiers: Account<'info, LockTiers>,
+    #[account(mut, seeds = [STAKING_POOL_SEED], bump = staking_pool.bump)]
+    pub staking_pool: Account<'info, StakingPool>,
+    #[account(mut)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = mint,
+        has_one = stake_vault,
+        has_one = reward_vault
+    )]
+    pub config: Account<'info, TokenConfig>,
+    pub token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
+#[instruction(index: u32)]
 pub struct Unstake<'info> {
     #[account(mut)]
     pub owner: Signer<'info>,
     #[account(mut)]
-    pub stake_vault: Account<'info, TokenAccount>,
+    pub stake_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, token::mint = mint)]
+    pub owner_token: InterfaceAccount<'info, TokenAccount>,
     #[account(mut)]
-    pub owner_token: Account<'info, TokenAccount>,
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
     #[account(mut)]
-    pub authority: Signer<'info>,
+    pub treasury_wallet: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: PDA that owns the stake, reward and vesting escrow vaults
+    #[account(seeds = [VAULT_AUTHORITY_SEED], bump = config.vault_authority_bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [STAKE_SEED, owner.key().as_ref(), &index.to_le_bytes()],
+        bump,
+        has_one = owner
+    )]
+    pub user_stake: Account<'info, UserStakeInfo>,
+    #[account(seeds = [LOCK_TIERS_SEED], bump = lock_tiers.bump)]
+    pub lock_tiers: Account<'info, LockTiers>,
+    #[account(mut, seeds = [STAKING_POOL_SEED], bump = staking_pool.bump)]
+    pub staking_pool: Account<'info, StakingPool>,
+    #[account(mut)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = mint,
+        has_one = stake_vault,
+        has_one = reward_vault,
+        has_one = treasury_wallet
+    )]
+    pub config: Account<'info, TokenConfig>,
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+#[instruction(index: u32)]
+pub struct ClaimRewards<'info> {
+    pub owner: Signer<'info>,
+    #[account(mut, token::mint = mint)]
+    pub owner_token: InterfaceAccount<'info, TokenAccount>,
     #[account(mut)]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: PDA that owns the stake, reward and vesting escrow vaults
+    #[account(seeds = [VAULT_AUTHORITY_SEED], bump = config.vault_authority_bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [STAKE_SEED, owner.key().as_ref(), &index.to_le_bytes()],
+        bump,
+        has_one = owner
+    )]
     pub user_stake: Account<'info, UserStakeInfo>,
-    pub token_program: Program<'info, Token>,
+    #[account(mut, seeds = [STAKING_POOL_SEED], bump = staking_pool.bump)]
+    pub staking_pool: Account<'info, StakingPool>,
+    #[account(mut)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(seeds = [CONFIG_SEED], bump = config.bump, has_one = mint, has_one = reward_vault)]
+    pub config: Account<'info, TokenConfig>,
+    pub token_program: Program<'info, Token2022>,
 }
 
 #[derive(Accounts)]
+#[instruction(amount: u64, recipient_chain: u16)]
 pub struct BridgeOut<'info> {
     #[account(mut)]
     pub owner: Signer<'info>,
     #[account(mut)]
-    pub from: Account<'info, TokenAccount>,
+    pub from: InterfaceAccount<'info, TokenAccount>,
     #[account(mut)]
-    pub mint: Account<'info, Mint>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = mint,
+        has_one = treasury_wallet
+    )]
+    pub config: Account<'info, TokenConfig>,
+    /// Peer the transfer is sent to; transfers to unregistered chains fail
+    #[account(mut, seeds = [FOREIGN_EMITTER_SEED, &recipient_chain.to_be_bytes()], bump)]
+    pub foreign_emitter: Account<'info, ForeignEmitter>,
     #[account(mut)]
-    pub token: Account<'info, CapySolanaToken>,
-    pub wormhole_program: Program<'info, wormhole::Wormhole>,
-    pub config: Account<'info, wormhole::Config>,
+    pub treasury_wallet: InterfaceAccount<'info, TokenAccount>,
+    pub wormhole_program: Program<'info, wormhole::program::Wormhole>,
+    #[account(
+        mut,
+        address = config.wormhole_config.bridge @ TokenError::WrongWormholeBridge,
+        seeds = [wormhole::BridgeData::SEED_PREFIX],
+        bump,
+        seeds::program = wormhole_program.key()
+    )]
+    pub wormhole_bridge: Account<'info, wormhole::BridgeData>,
+    /// Fresh keypair; the core bridge creates the message account
     #[account(mut)]
-    pub message: Account<'info, wormhole::Message>,
-    pub emitter: Account<'info, wormhole::Emitter>,
-    pub sequence: Account<'info, wormhole::Sequence>,
-    pub payer: Account<'info, TokenAccount>,
-    pub fee_collector: Account<'info, TokenAccount>,
+    pub message: Signer<'info>,
+    /// CHECK: PDA that signs the program's Wormhole messages
+    #[account(seeds = [wormhole::SEED_PREFIX_EMITTER], bump)]
+    pub emitter: UncheckedAccount<'info>,
+    /// CHECK: Wormhole sequence of the emitter, read by `next_sequence`;
+    /// created by the core bridge with the emitter's first message
+    #[account(
+        mut,
+        seeds = [wormhole::SequenceTracker::SEED_PREFIX, emitter.key().as_ref()],
+        bump,
+        seeds::program = wormhole_program.key()
+    )]
+    pub sequence: UncheckedAccount<'info>,
+    /// Pays the Wormhole message fee and the message account rent in SOL
+    #[account(mut, owner = system_program::ID)]
+    pub payer: Signer<'info>,
+    /// CHECK: Wormhole fee collector, checked by the core bridge
+    #[account(
+        mut,
+        seeds = [wormhole::FeeCollector::SEED_PREFIX],
+        bump,
+        seeds::program = wormhole_program.key()
+    )]
+    pub fee_collector: UncheckedAccount<'info>,
+    /// Record of the transfer for refunds, keyed by the message account so
+    /// it does not depend on the sequence the core bridge assigns
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + OutboundTransfer::LEN,
+        seeds = [OUTBOUND_TRANSFER_SEED, message.key().as_ref()],
+        bump
+    )]
+    pub outbound_transfer: Account<'info, OutboundTransfer>,
     pub clock: Sysvar<'info, Clock>,
     pub rent: Sysvar<'info, Rent>,
     pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
+    pub token_program: Program<'info, Token2022>,
 }
 
 #[derive(Accounts)]
+#[instruction(chain: u16)]
+pub struct RegisterEmitter<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.bump
+    )]
+    pub config: Account<'info, TokenConfig>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + ForeignEmitter::LEN,
+        seeds = [FOREIGN_EMITTER_SEED, &chain.to_be_bytes()],
+        bump
+    )]
+    pub foreign_emitter: Account<'info, ForeignEmitter>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(vaa_hash: [u8; 32])]
 pub struct BridgeIn<'info> {
     #[account(mut)]
-    pub recipient: Account<'info, TokenAccount>,
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        token::mint = mint,
+        constraint = recipient.owner == Pubkey::new_from_array(posted_vaa.data().recipient)
+            @ TokenError::RecipientMismatch
+    )]
+    pub recipient: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(seeds = [CONFIG_SEED], bump = config.bump, has_one = mint)]
+    pub config: Account<'info, TokenConfig>,
+    /// CHECK: PDA that signs every mint of the token
+    #[account(seeds = [MINT_AUTHORITY_SEED], bump = config.mint_authority_bump)]
+    pub mint_authority: UncheckedAccount<'info>,
+    #[account(
+        seeds = [wormhole::SEED_PREFIX_POSTED_VAA, &vaa_hash],
+        bump,
+        seeds::program = wormhole_program.key()
+    )]
+    pub posted_vaa: Account<'info, wormhole::PostedVaa<BridgeMessage>>,
+    #[account(
+        mut,
+        seeds = [FOREIGN_EMITTER_SEED, &posted_vaa.emitter_chain().to_be_bytes()],
+        bump,
+        constraint = foreign_emitter.address == *posted_vaa.emitter_address() @ TokenError::UnknownEmitter
+    )]
+    pub foreign_emitter: Account<'info, ForeignEmitter>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + VaaClaim::LEN,
+        seeds = [
+            CLAIM_SEED,
+            &posted_vaa.emitter_chain().to_be_bytes(),
+            posted_vaa.emitter_address().as_ref(),
+            &posted_vaa.sequence().to_be_bytes(),
+        ],
+        bump
+    )]
+    pub claim: Account<'info, VaaClaim>,
+    pub wormhole_program: Program<'info, wormhole::program::Wormhole>,
+    pub token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReleaseInboundTransfer<'info> {
+    pub caller: Signer<'info>,
+    #[account(mut, address = claim.recipient @ TokenError::RecipientMismatch)]
+    pub recipient: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(seeds = [CONFIG_SEED], bump = config.bump, has_one = mint)]
+    pub config: Account<'info, TokenConfig>,
+    /// CHECK: PDA that signs every mint of the token
+    #[account(seeds = [MINT_AUTHORITY_SEED], bump = config.mint_authority_bump)]
+    pub mint_authority: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [
+            CLAIM_SEED,
+            &claim.emitter_chain.to_be_bytes(),
+            claim.emitter_address.as_ref(),
+            &claim.sequence.to_be_bytes(),
+        ],
+        bump
+    )]
+    pub claim: Account<'info, VaaClaim>,
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+#[instruction(chain: u16)]
+pub struct SetRateLimits<'info> {
+    pub authority: Signer<'info>,
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, TokenConfig>,
+    #[account(mut, seeds = [FOREIGN_EMITTER_SEED, &chain.to_be_bytes()], bump)]
+    pub foreign_emitter: Account<'info, ForeignEmitter>,
+}
+
+#[derive(Accounts)]
+#[instruction(vaa_hash: [u8; 32])]
+pub struct RefundTransfer<'info> {
+    pub sender: Signer<'info>,
+    #[account(mut, token::mint = mint, token::authority = sender)]
+    pub sender_token: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(seeds = [CONFIG_SEED], bump = config.bump, has_one = mint)]
+    pub config: Account<'info, TokenConfig>,
+    /// CHECK: PDA that signs every mint of the token
+    #[account(seeds = [MINT_AUTHORITY_SEED], bump = config.mint_authority_bump)]
+    pub mint_authority: UncheckedAccount<'info>,
+    #[account(
+        seeds = [wormhole::SEED_PREFIX_POSTED_VAA, &vaa_hash],
+        bump,
+        seeds::program = wormhole_program.key()
+    )]
+    pub posted_vaa: Account<'info, wormhole::PostedVaa<TransferRejected>>,
+    /// Peer the transfer was sent to; only it can reject the transfer
+    #[account(
+        mut,
+        seeds = [FOREIGN_EMITTER_SEED, &outbound_transfer.recipient_chain.to_be_bytes()],
+        bump,
+        constraint = foreign_emitter.chain == posted_vaa.emitter_chain()
+            && foreign_emitter.address == *posted_vaa.emitter_address()
+            @ TokenError::UnknownEmitter
+    )]
+    pub foreign_emitter: Account<'info, ForeignEmitter>,
+    #[account(
+        mut,
+        seeds = [OUTBOUND_TRANSFER_SEED, outbound_transfer.message.as_ref()],
+        bump,
+        has_one = sender
+    )]
+    pub outbound_transfer: Account<'info, OutboundTransfer>,
+    pub wormhole_program: Program<'info, wormhole::program::Wormhole>,
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+#[instruction(chain: u16)]
+pub struct RemainingCapacity<'info> {
+    #[account(seeds = [FOREIGN_EMITTER_SEED, &chain.to_be_bytes()], bump)]
+    pub foreign_emitter: Account<'info, ForeignEmitter>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimVesting<'info> {
+    pub beneficiary: Signer<'info>,
+    #[account(
+        mut,
+        constraint = beneficiary_token.owner == beneficiary.key() @ VestingError::Unauthorized
+    )]
+    pub beneficiary_token: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [VESTING_SEED, beneficiary_token.key().as_ref()],
+        bump,
+        constraint = user_vesting.owner == beneficiary_token.key() @ VestingError::WrongBeneficiary
+    )]
+    pub user_vesting: Account<'info, UserVestingInfo>,
+    #[account(mut, seeds = [VESTING_ESCROW_SEED, beneficiary_token.key().as_ref()], bump)]
+    pub escrow: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(seeds = [CONFIG_SEED], bump = config.bump, has_one = mint)]
+    pub config: Account<'info, TokenConfig>,
+    /// CHECK: PDA that owns the stake, reward and vesting escrow vaults
+    #[account(seeds = [VAULT_AUTHORITY_SEED], bump = config.vault_authority_bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct CollectTransferFees<'info> {
+    #[account(mut)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub treasury_wallet: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = mint,
+        has_one = treasury_wallet
+    )]
+    pub config: Account<'info, TokenConfig>,
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump = config.bump
+    )]
+    pub config: Account<'info, TokenConfig>,
+}
+
+#[derive(Accounts)]
+pub struct ScheduleChange<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(mut, seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, TokenConfig>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + PendingChange::LEN,
+        seeds = [PENDING_CHANGE_SEED, &config.next_change_id.to_le_bytes()],
+        bump
+    )]
+    pub pending_change: Account<'info, PendingChange>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteChange<'info> {
+    #[account(mut)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, seeds = [CONFIG_SEED], bump = config.bump, has_one = mint)]
+    pub config: Account<'info, TokenConfig>,
+    #[account(
+        mut,
+        seeds = [PENDING_CHANGE_SEED, &pending_change.id.to_le_bytes()],
+        bump = pending_change.bump,
+        has_one = scheduled_by,
+        close = scheduled_by
+    )]
+    pub pending_change: Account<'info, PendingChange>,
+    /// CHECK: receives the rent of the executed change; matched by has_one
+    #[account(mut)]
+    pub scheduled_by: UncheckedAccount<'info>,
+    #[account(mut, seeds = [STAKING_POOL_SEED], bump = staking_pool.bump)]
+    pub staking_pool: Account<'info, StakingPool>,
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct CancelChange<'info> {
+    pub authority: Signer<'info>,
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, TokenConfig>,
+    #[account(
+        mut,
+        seeds = [PENDING_CHANGE_SEED, &pending_change.id.to_le_bytes()],
+        bump = pending_change.bump,
+        has_one = scheduled_by,
+        close = scheduled_by
+    )]
+    pub pending_change: Account<'info, PendingChange>,
+    /// CHECK: receives the rent of the cancelled change; matched by has_one
+    #[account(mut)]
+    pub scheduled_by: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetTreasuryWallet<'info> {
+    pub authority: Signer<'info>,
+    #[account(token::mint = config.mint)]
+    pub new_treasury_wallet: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump = config.bump
+    )]
+    pub config: Account<'info, TokenConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetLockTiers<'info> {
+    pub authority: Signer<'info>,
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, TokenConfig>,
+    #[account(mut, seeds = [LOCK_TIERS_SEED], bump = lock_tiers.bump)]
+    pub lock_tiers: Account<'info, LockTiers>,
+}
+
+#[derive(Accounts)]
+pub struct SetExemption<'info> {
+    pub authority: Signer<'info>,
+    /// Pays for the exemption account on its first update
     #[account(mut)]
-    pub mint: Account<'info, Mint>,
+    pub payer: Signer<'info>,
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, TokenConfig>,
+    /// CHECK: checked by the transfer hook program
+    pub hook_config: UncheckedAccount<'info>,
+    /// CHECK: created or updated by the transfer hook program
     #[account(mut)]
-    pub token: Account<'info, CapySolanaToken>,
-    pub wormhole_program: Program<'info, wormhole::Wormhole>,
+    pub exemption: UncheckedAccount<'info>,
+    pub transfer_hook_program: Program<'info, CapySolanaTransferHook>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetMaxTransferAmount<'info> {
     pub authority: Signer<'info>,
-    pub token_program: Program<'info, Token>,
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, TokenConfig>,
+    /// CHECK: checked by the transfer hook program
+    #[account(mut)]
+    pub hook_config: UncheckedAccount<'info>,
+    pub transfer_hook_program: Program<'info, CapySolanaTransferHook>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    pub pending_authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        constraint = config.pending_authority == pending_authority.key()
+            @ TokenError::NotPendingAuthority
+    )]
+    pub config: Account<'info, TokenConfig>,
 }
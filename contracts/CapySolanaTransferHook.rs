@@ -0,0 +1,319 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_2022::spl_token_2022::extension::{
+    transfer_hook::{TransferHook as TransferHookExtension, TransferHookAccount},
+    BaseStateWithExtensions, StateWithExtensions,
+};
+use anchor_spl::token_2022::spl_token_2022::state::{Account as SplTokenAccount, Mint as SplMint};
+use anchor_spl::token_interface::{Mint, TokenAccount};
+use spl_tlv_account_resolution::{
+    account::ExtraAccountMeta, seeds::Seed, state::ExtraAccountMetaList,
+};
+use spl_transfer_hook_interface::instruction::ExecuteInstruction;
+
+declare_id!("CapyHookNBty7U4pE8jWgSjD745E5bes2gzGgaYu8fR8");
+
+// Transfer limits
+pub const MAX_TRANSFER_AMOUNT: u64 = 1_000_000 * 1_000_000_000; // 1M tokens
+
+// PDA seeds
+pub const EXTRA_ACCOUNT_METAS_SEED: &[u8] = b"extra-account-metas";
+pub const HOOK_CONFIG_SEED: &[u8] = b"hook_config";
+pub const EXEMPTION_SEED: &[u8] = b"exemption";
+
+// Offset of the owner field in an SPL token account
+const TOKEN_ACCOUNT_OWNER_OFFSET: u8 = 32;
+
+#[program]
+pub mod capy_solana_transfer_hook {
+    use super::*;
+
+    /// Signed by the mint's transfer hook authority, so only the owner of the
+    /// mint can set up the hook and choose the authority that manages it.
+    pub fn initialize_extra_account_meta_list(
+        ctx: Context<InitializeExtraAccountMetaList>,
+        authority: Pubkey,
+    ) -> Result<()> {
+        assert_hook_authority(&ctx.accounts.mint, &ctx.accounts.hook_authority)?;
+
+        let hook_config = &mut ctx.accounts.hook_config;
+        hook_config.authority = authority;
+        hook_config.mint = ctx.accounts.mint.key();
+        hook_config.max_transfer_amount = MAX_TRANSFER_AMOUNT;
+        hook_config.bump = ctx.bumps.hook_config;
+
+        ExtraAccountMetaList::init::<ExecuteInstruction>(
+            &mut ctx.accounts.extra_account_meta_list.try_borrow_mut_data()?,
+            &extra_account_metas()?,
+        )?;
+
+        Ok(())
+    }
+
+    #[interface(spl_transfer_hook_interface::execute)]
+    pub fn transfer_hook(ctx: Context<TransferHook>, amount: u64) -> Result<()> {
+        assert_is_transferring(&ctx.accounts.source_token)?;
+
+        // Same rule as the EVM `_transfer`: the limit is skipped when either
+        // side of the transfer is exempt
+        if is_exempt(&ctx.accounts.source_exemption)?
+            || is_exempt(&ctx.accounts.destination_exemption)?
+        {
+            return Ok(());
+        }
+
+        require!(
+            amount <= ctx.accounts.hook_config.max_transfer_amount,
+            HookError::ExceedsMaximum
+        );
+
+        Ok(())
+    }
+
+    pub fn set_exemption(ctx: Context<SetExemption>, owner: Pubkey, exempt: bool) -> Result<()> {
+        let exemption = &mut ctx.accounts.exemption;
+        exemption.owner = owner;
+        exemption.exempt = exempt;
+
+        emit!(ExemptionUpdated { owner, exempt });
+
+        Ok(())
+    }
+
+    pub fn set_max_transfer_amount(ctx: Context<UpdateHookConfig>, amount: u64) -> Result<()> {
+        require!(amount > 0, HookError::ZeroAmount);
+        ctx.accounts.hook_config.max_transfer_amount = amount;
+
+        emit!(MaxTransferAmountUpdated { amount });
+
+        Ok(())
+    }
+}
+
+#[account]
+pub struct HookConfig {
+    pub authority: Pubkey,
+    pub mint: Pubkey,
+    pub max_transfer_amount: u64,
+    pub bump: u8,
+}
+
+impl HookConfig {
+    pub const LEN: usize = 32 + 32 + 8 + 1;
+}
+
+#[account]
+pub struct Exemption {
+    pub owner: Pubkey,
+    pub exempt: bool,
+}
+
+impl Exemption {
+    pub const LEN: usize = 32 + 1;
+}
+
+/// Accounts appended to every Token-2022 transfer of the mint: the hook config
+/// and the exemption PDAs of the source and destination token account owners.
+/// Exemptions are seeded by the mint so they only apply to the mint they were
+/// granted for.
+pub fn extra_account_metas() -> Result<Vec<ExtraAccountMeta>> {
+    Ok(vec![
+        ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal {
+                    bytes: HOOK_CONFIG_SEED.to_vec(),
+                },
+                Seed::AccountKey { index: 1 },
+            ],
+            false,
+            false,
+        )?,
+        ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal {
+                    bytes: EXEMPTION_SEED.to_vec(),
+                },
+                Seed::AccountKey { index: 1 },
+                Seed::AccountData {
+                    account_index: 0,
+                    data_index: TOKEN_ACCOUNT_OWNER_OFFSET,
+                    length: 32,
+                },
+            ],
+            false,
+            false,
+        )?,
+        ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal {
+                    bytes: EXEMPTION_SEED.to_vec(),
+                },
+                Seed::AccountKey { index: 1 },
+                Seed::AccountData {
+                    account_index: 2,
+                    data_index: TOKEN_ACCOUNT_OWNER_OFFSET,
+                    length: 32,
+                },
+            ],
+            false,
+            false,
+        )?,
+    ])
+}
+
+fn assert_hook_authority(mint: &InterfaceAccount<Mint>, hook_authority: &Signer) -> Result<()> {
+    let mint_info = mint.to_account_info();
+    let data = mint_info.try_borrow_data()?;
+    let mint = StateWithExtensions::<SplMint>::unpack(&data)?;
+    let extension = mint.get_extension::<TransferHookExtension>()?;
+    require!(
+        Option::<Pubkey>::from(extension.program_id) == Some(crate::ID),
+        HookError::HookNotConfigured
+    );
+    require!(
+        Option::<Pubkey>::from(extension.authority) == Some(hook_authority.key()),
+        HookError::Unauthorized
+    );
+
+    Ok(())
+}
+
+fn assert_is_transferring(source_token: &InterfaceAccount<TokenAccount>) -> Result<()> {
+    let source_info = source_token.to_account_info();
+    let data = source_info.try_borrow_data()?;
+    let account = StateWithExtensions::<SplTokenAccount>::unpack(&data)?;
+    let extension = account.get_extension::<TransferHookAccount>()?;
+    require!(
+        bool::from(extension.transferring),
+        HookError::NotTransferring
+    );
+
+    Ok(())
+}
+
+fn is_exempt(exemption: &UncheckedAccount) -> Result<bool> {
+    if exemption.owner != &crate::ID || exemption.data_is_empty() {
+        return Ok(false);
+    }
+
+    let data = exemption.try_borrow_data()?;
+    let exemption = Exemption::try_deserialize(&mut &data[..])?;
+    Ok(exemption.exempt)
+}
+
+#[event]
+pub struct ExemptionUpdated {
+    pub owner: Pubkey,
+    pub exempt: bool,
+}
+
+#[event]
+pub struct MaxTransferAmountUpdated {
+    pub amount: u64,
+}
+
+#[error_code]
+pub enum HookError {
+    #[msg("Transfer amount exceeds maximum")]
+    ExceedsMaximum,
+    #[msg("Transfer hook was not invoked by a token transfer")]
+    NotTransferring,
+    #[msg("Amount cannot be zero")]
+    ZeroAmount,
+    #[msg("Signer is not the hook authority")]
+    Unauthorized,
+    #[msg("Mint does not use this program as its transfer hook")]
+    HookNotConfigured,
+}
+
+#[derive(Accounts)]
+pub struct InitializeExtraAccountMetaList<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// Transfer hook authority recorded in the mint's extension
+    pub hook_authority: Signer<'info>,
+    /// CHECK: initialized with the TLV extra account meta list
+    #[account(
+        init,
+        payer = payer,
+        space = ExtraAccountMetaList::size_of(3)?,
+        seeds = [EXTRA_ACCOUNT_METAS_SEED, mint.key().as_ref()],
+        bump
+    )]
+    pub extra_account_meta_list: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + HookConfig::LEN,
+        seeds = [HOOK_CONFIG_SEED, mint.key().as_ref()],
+        bump
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub system_program: Program<'info, System>,
+}
+
+// Order of the first five accounts is fixed by the transfer hook interface
+#[derive(Accounts)]
+pub struct TransferHook<'info> {
+    #[account(token::mint = mint)]
+    pub source_token: InterfaceAccount<'info, TokenAccount>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(token::mint = mint)]
+    pub destination_token: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: source token account owner or delegate
+    pub owner: UncheckedAccount<'info>,
+    /// CHECK: TLV extra account meta list
+    #[account(seeds = [EXTRA_ACCOUNT_METAS_SEED, mint.key().as_ref()], bump)]
+    pub extra_account_meta_list: UncheckedAccount<'info>,
+    #[account(seeds = [HOOK_CONFIG_SEED, mint.key().as_ref()], bump = hook_config.bump)]
+    pub hook_config: Account<'info, HookConfig>,
+    /// CHECK: exemption PDA of the source owner; may be uninitialized
+    #[account(
+        seeds = [EXEMPTION_SEED, mint.key().as_ref(), source_token.owner.as_ref()],
+        bump
+    )]
+    pub source_exemption: UncheckedAccount<'info>,
+    /// CHECK: exemption PDA of the destination owner; may be uninitialized
+    #[account(
+        seeds = [EXEMPTION_SEED, mint.key().as_ref(), destination_token.owner.as_ref()],
+        bump
+    )]
+    pub destination_exemption: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(owner: Pubkey)]
+pub struct SetExemption<'info> {
+    /// Hook authority; the token program's config PDA signs through CPI
+    pub authority: Signer<'info>,
+    /// Pays for the exemption account, which a PDA authority cannot
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [HOOK_CONFIG_SEED, hook_config.mint.as_ref()],
+        bump = hook_config.bump,
+        has_one = authority @ HookError::Unauthorized
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + Exemption::LEN,
+        seeds = [EXEMPTION_SEED, hook_config.mint.as_ref(), owner.as_ref()],
+        bump
+    )]
+    pub exemption: Account<'info, Exemption>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateHookConfig<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [HOOK_CONFIG_SEED, hook_config.mint.as_ref()],
+        bump = hook_config.bump,
+        has_one = authority @ HookError::Unauthorized
+    )]
+    pub hook_config: Account<'info, HookConfig>,
+}
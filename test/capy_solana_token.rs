@@ -0,0 +1,2171 @@
+//! In-process integration tests for `capy_solana_token`.
+//!
+//! The token program and the transfer hook run natively inside
+//! solana-program-test next to a mock Wormhole core program, so the suite
+//! needs neither a validator nor a Wormhole network. Inbound VAAs are signed
+//! by the local guardian set in `capy_solana_client::mock_wormhole`.
+
+mod events;
+
+use anchor_lang::solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    rent::Rent,
+    sysvar::Sysvar,
+};
+use anchor_lang::{
+    prelude::borsh, AccountDeserialize, AnchorDeserialize, AnchorSerialize, InstructionData,
+    ToAccountMetas,
+};
+use anchor_spl::associated_token::spl_associated_token_account::instruction::create_associated_token_account;
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    extension::StateWithExtensions,
+    state::{Account as SplTokenAccount, Mint as SplMint},
+};
+use capy_solana_client::mock_wormhole::{
+    posted_account_data, MockGuardianSet, PostedVaaAccount, SignedVaa, VaaBody,
+};
+use capy_solana_client::{
+    decode_posted_message, decode_posted_vaa, instructions, pda, transfer_hook_accounts,
+    AllocationWallets, PostedMeta,
+};
+use capy_solana_token::{
+    AuthorityProposed, AuthoritySignersUpdated, AuthorityTransferred, BridgeMessage,
+    ChangeCancelled, ChangeExecuted, ConfigParameter, ConfigUpdated, FeesCollected, ForeignEmitter,
+    LockTiers, OutboundTransfer, ParameterChange, ParameterValue, PeerRegistered,
+    RateLimitCapacity, StakeError, StakingPool, TokenConfig, TokenError, TransferRejected,
+    TrimmedAmount, UserStakeInfo, UserVestingInfo, VaaClaim, VestingClaimed, VestingError,
+    VestingInfo, WormholeConfig, DECIMALS, DEFAULT_EARLY_EXIT_PENALTY_BPS, DEFAULT_EMISSION_RATE,
+    DEFAULT_LOCK_TIERS, DEVELOPMENT_ALLOCATION, DEVELOPMENT_VESTING_DURATION, INBOUND_QUEUE_DELAY,
+    INITIAL_SUPPLY, MARKETING_ALLOCATION, MARKETING_VESTING_PERIOD, MARKETING_VESTING_QUARTERS,
+    MAX_BRIDGE_FEE_BPS, MAX_EMISSION_RATE, MAX_TRANSFER_TAX_RATE, NTT_TRANSCEIVER_PREFIX,
+    NTT_TRANSFER_PREFIX, RATE_LIMIT_DURATION, STAKING_ALLOCATION, TEAM_ALLOCATION,
+    TEAM_CLIFF_PERIOD, TEAM_VESTING_DURATION, TIMELOCK_DELAY, TRANSFER_TAX_RATE, TRIMMED_DECIMALS,
+};
+use capy_solana_transfer_hook::{Exemption, HookError, MAX_TRANSFER_AMOUNT};
+use events::Events;
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    bpf_loader_upgradeable,
+    clock::Clock,
+    compute_budget::ComputeBudgetInstruction,
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_instruction, system_program,
+    transaction::{Transaction, TransactionError},
+};
+use wormhole_anchor_sdk::wormhole;
+
+const FOREIGN_CHAIN: u16 = 2; // Ethereum
+const FOREIGN_EMITTER: [u8; 32] = [0xee; 32];
+const FOREIGN_MANAGER: [u8; 32] = [0xdd; 32];
+const FOREIGN_DECIMALS: u8 = 18;
+const POST_MESSAGE_INSTRUCTION: u8 = 1;
+const WORMHOLE_FEE: u64 = 100; // Lamports per message
+const FEE_COLLECTOR_BALANCE: u64 = 1_000_000_000;
+
+/// Scales whole tokens to base units.
+fn tokens(amount: u64) -> u64 {
+    amount * 10u64.pow(DECIMALS as u32)
+}
+
+/// Fee Token-2022 withholds on a transfer of `amount`, rounded up like the
+/// transfer fee extension does.
+fn transfer_fee(amount: u64) -> u64 {
+    (amount as u128 * TRANSFER_TAX_RATE as u128 * 100).div_ceil(10_000) as u64
+}
+
+// Anchor ties the account slice lifetime to the account infos, which
+// processor! cannot express; leaking the slice is fine for a test process
+fn capy_solana_token_entry(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    capy_solana_token::entry(program_id, accounts, data)
+}
+
+fn capy_solana_transfer_hook_entry(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    capy_solana_transfer_hook::entry(program_id, accounts, data)
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+struct PostMessageData {
+    nonce: u32,
+    payload: Vec<u8>,
+    consistency_level: u8,
+}
+
+/// Mock of the Wormhole core bridge. Only `post_message` is implemented: like
+/// the core bridge it requires the emitter's signature and the message fee in
+/// the fee collector, then stamps the emitter's next sequence and creates the
+/// message account in the core bridge layout. The emitter's sequence account
+/// is created with its first message.
+fn mock_wormhole_entry(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let (instruction, data) = data
+        .split_first()
+        .ok_or(ProgramError::InvalidInstructionData)?;
+    if *instruction != POST_MESSAGE_INSTRUCTION {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let post = PostMessageData::try_from_slice(data)?;
+
+    let bridge = &accounts[0];
+    let message = &accounts[1];
+    let emitter = &accounts[2];
+    let sequence_tracker = &accounts[3];
+    let payer = &accounts[4];
+    let fee_collector = &accounts[5];
+    if !message.is_signer || !emitter.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    // Bridge data: guardian set index, last fee collector balance,
+    // guardian set expiration time and message fee
+    {
+        let mut bridge_data = bridge.try_borrow_mut_data()?;
+        let last_lamports = u64::from_le_bytes(bridge_data[4..12].try_into().unwrap());
+        let fee = u64::from_le_bytes(bridge_data[16..24].try_into().unwrap());
+        if fee_collector.lamports() < last_lamports + fee {
+            return Err(ProgramError::InsufficientFunds);
+        }
+        bridge_data[4..12].copy_from_slice(&fee_collector.lamports().to_le_bytes());
+    }
+
+    if sequence_tracker.data_is_empty() {
+        let (_, bump) = Pubkey::find_program_address(
+            &[wormhole::SequenceTracker::SEED_PREFIX, emitter.key.as_ref()],
+            &wormhole::program::ID,
+        );
+        invoke_signed(
+            &system_instruction::create_account(
+                payer.key,
+                sequence_tracker.key,
+                Rent::get()?.minimum_balance(8),
+                8,
+                &wormhole::program::ID,
+            ),
+            accounts,
+            &[&[
+                wormhole::SequenceTracker::SEED_PREFIX,
+                emitter.key.as_ref(),
+                &[bump],
+            ]],
+        )?;
+    }
+    let sequence = {
+        let mut sequence_data = sequence_tracker.try_borrow_mut_data()?;
+        let sequence = u64::from_le_bytes(sequence_data[..8].try_into().unwrap());
+        sequence_data[..8].copy_from_slice(&(sequence + 1).to_le_bytes());
+        sequence
+    };
+
+    let now = Clock::get()?.unix_timestamp as u32;
+    let meta = PostedMeta {
+        version: 1,
+        consistency_level: post.consistency_level,
+        timestamp: now,
+        signature_set: Pubkey::default(),
+        posted_timestamp: now,
+        nonce: post.nonce,
+        sequence,
+        emitter_chain: wormhole::CHAIN_ID_SOLANA,
+        emitter_address: emitter.key.to_bytes(),
+    };
+    let bytes = posted_account_data(b"msg", &meta, &post.payload);
+    invoke(
+        &system_instruction::create_account(
+            payer.key,
+            message.key,
+            Rent::get()?.minimum_balance(bytes.len()),
+            bytes.len() as u64,
+            &wormhole::program::ID,
+        ),
+        accounts,
+    )?;
+    message.try_borrow_mut_data()?.copy_from_slice(&bytes);
+
+    Ok(())
+}
+
+fn assert_error(result: Result<(), BanksClientError>, code: u32) {
+    match result.expect_err("transaction should fail").unwrap() {
+        TransactionError::InstructionError(_, InstructionError::Custom(actual)) => {
+            assert_eq!(actual, code)
+        }
+        err => panic!("unexpected error: {err:?}"),
+    }
+}
+
+struct Harness {
+    context: ProgramTestContext,
+    treasury_owner: Keypair,
+    development: Keypair,
+    marketing: Keypair,
+    team: Keypair,
+    guardians: MockGuardianSet,
+}
+
+impl Harness {
+    /// Starts the bank and runs the full deployment: mint, hook, config,
+    /// allocations, lock tiers and a registered foreign emitter.
+    async fn new() -> Self {
+        events::capture();
+        let mut program_test = ProgramTest::new(
+            "capy_solana_token",
+            capy_solana_token::ID,
+            processor!(capy_solana_token_entry),
+        );
+        program_test.add_program(
+            "capy_solana_transfer_hook",
+            capy_solana_transfer_hook::ID,
+            processor!(capy_solana_transfer_hook_entry),
+        );
+        program_test.add_program(
+            "mock_wormhole",
+            wormhole::program::ID,
+            processor!(mock_wormhole_entry),
+        );
+        program_test.prefer_bpf(false);
+
+        // Core bridge state that a real deployment already has
+        let mut bridge_data = Vec::new();
+        (0u32, FEE_COLLECTOR_BALANCE, 86_400u32, WORMHOLE_FEE)
+            .serialize(&mut bridge_data)
+            .unwrap();
+        program_test.add_account(
+            pda::wormhole_bridge(&wormhole::program::ID),
+            Account {
+                lamports: 1_000_000_000,
+                data: bridge_data,
+                owner: wormhole::program::ID,
+                ..Account::default()
+            },
+        );
+        program_test.add_account(
+            pda::wormhole_fee_collector(&wormhole::program::ID),
+            Account {
+                lamports: FEE_COLLECTOR_BALANCE,
+                ..Account::default()
+            },
+        );
+
+        let mut harness = Self {
+            context: program_test.start_with_context().await,
+            treasury_owner: Keypair::new(),
+            development: Keypair::new(),
+            marketing: Keypair::new(),
+            team: Keypair::new(),
+            guardians: MockGuardianSet::new(0, 19),
+        };
+        let authority = harness.authority();
+        harness.set_upgrade_authority(&authority);
+        harness.deploy().await;
+        harness
+    }
+
+    /// Writes the program's ProgramData account as a deploy through the
+    /// upgradeable loader leaves it; the test programs run as builtins.
+    fn set_upgrade_authority(&mut self, authority: &Pubkey) {
+        // UpgradeableLoaderState::ProgramData { slot, upgrade_authority_address }
+        let mut data = 3u32.to_le_bytes().to_vec();
+        data.extend_from_slice(&0u64.to_le_bytes());
+        data.push(1);
+        data.extend_from_slice(authority.as_ref());
+        let rent = Rent::default();
+        self.context.set_account(
+            &pda::program_data(),
+            &Account {
+                lamports: rent.minimum_balance(data.len()),
+                data,
+                owner: bpf_loader_upgradeable::ID,
+                ..Account::default()
+            }
+            .into(),
+        );
+    }
+
+    fn authority(&self) -> Pubkey {
+        self.context.payer.pubkey()
+    }
+
+    fn authority_keypair(&self) -> Keypair {
+        self.context.payer.insecure_clone()
+    }
+
+    fn treasury_wallet(&self) -> Pubkey {
+        pda::token_account(&self.treasury_owner.pubkey())
+    }
+
+    fn development_wallet(&self) -> Pubkey {
+        pda::token_account(&self.development.pubkey())
+    }
+
+    fn marketing_wallet(&self) -> Pubkey {
+        pda::token_account(&self.marketing.pubkey())
+    }
+
+    fn team_wallet(&self) -> Pubkey {
+        pda::token_account(&self.team.pubkey())
+    }
+
+    async fn transaction(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Transaction {
+        let mut all_instructions =
+            vec![ComputeBudgetInstruction::set_compute_unit_limit(1_400_000)];
+        all_instructions.extend_from_slice(instructions);
+
+        let blockhash = self.context.get_new_latest_blockhash().await.unwrap();
+        let payer = self.context.payer.pubkey();
+        let mut all_signers = vec![&self.context.payer];
+        all_signers.extend(signers.iter().filter(|signer| signer.pubkey() != payer));
+        Transaction::new_signed_with_payer(&all_instructions, Some(&payer), &all_signers, blockhash)
+    }
+
+    async fn send(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<(), BanksClientError> {
+        let transaction = self.transaction(instructions, signers).await;
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+    }
+
+    /// Like `send`, but returns the events the transaction emitted.
+    async fn send_with_events(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<Events, BanksClientError> {
+        let transaction = self.transaction(instructions, signers).await;
+        let processed = self
+            .context
+            .banks_client
+            .process_transaction_with_metadata(transaction)
+            .await?;
+        processed.result?;
+        let logs = processed.metadata.unwrap().log_messages;
+        Ok(Events::from_logs(&logs))
+    }
+
+    async fn account<T: AccountDeserialize>(&mut self, address: Pubkey) -> T {
+        let account = self
+            .context
+            .banks_client
+            .get_account(address)
+            .await
+            .unwrap()
+            .expect("account exists");
+        T::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    async fn balance(&mut self, token_account: Pubkey) -> u64 {
+        let account = self
+            .context
+            .banks_client
+            .get_account(token_account)
+            .await
+            .unwrap()
+            .expect("token account exists");
+        StateWithExtensions::<SplTokenAccount>::unpack(&account.data)
+            .unwrap()
+            .base
+            .amount
+    }
+
+    async fn supply(&mut self) -> u64 {
+        let account = self
+            .context
+            .banks_client
+            .get_account(pda::mint())
+            .await
+            .unwrap()
+            .unwrap();
+        StateWithExtensions::<SplMint>::unpack(&account.data)
+            .unwrap()
+            .base
+            .supply
+    }
+
+    async fn warp(&mut self, seconds: i64) {
+        let mut clock: Clock = self.context.banks_client.get_sysvar().await.unwrap();
+        clock.unix_timestamp += seconds;
+        self.context.set_sysvar(&clock);
+    }
+
+    async fn deploy(&mut self) {
+        let authority = self.authority();
+        let mint = pda::mint();
+
+        let initialize_mint = instructions::initialize_mint(&authority);
+        self.send(&[initialize_mint], &[]).await.unwrap();
+
+        // Vesting beneficiaries receive their claims in their own token accounts
+        let beneficiaries = [
+            self.development.pubkey(),
+            self.marketing.pubkey(),
+            self.team.pubkey(),
+        ];
+        let create_wallets: Vec<_> = beneficiaries
+            .iter()
+            .map(|owner| {
+                create_associated_token_account(&authority, owner, &mint, &spl_token_2022::ID)
+            })
+            .collect();
+        self.send(&create_wallets, &[]).await.unwrap();
+
+        let wallets = AllocationWallets {
+            treasury_owner: self.treasury_owner.pubkey(),
+            development_wallet: self.development_wallet(),
+            marketing_wallet: self.marketing_wallet(),
+            team_wallet: self.team_wallet(),
+        };
+        let wormhole_config = WormholeConfig {
+            bridge: pda::wormhole_bridge(&wormhole::program::ID),
+            message_fee: WORMHOLE_FEE,
+            consistency_level: 1,
+        };
+        let initialize = instructions::initialize(&authority, &wallets, wormhole_config);
+        self.send(&[initialize], &[]).await.unwrap();
+
+        let register_emitter = Self::register_emitter(&authority, FOREIGN_EMITTER, FOREIGN_MANAGER);
+        self.send(&[register_emitter], &[]).await.unwrap();
+    }
+
+    /// Creates a funded user with a token account holding `amount` sent from
+    /// the treasury, net of the transfer fee.
+    async fn user(&mut self, amount: u64) -> Keypair {
+        let user = Keypair::new();
+        let payer = self.authority();
+        let fund = system_instruction::transfer(&payer, &user.pubkey(), 1_000_000_000);
+        let create_token_account = create_associated_token_account(
+            &payer,
+            &user.pubkey(),
+            &pda::mint(),
+            &spl_token_2022::ID,
+        );
+        self.send(&[fund, create_token_account], &[]).await.unwrap();
+
+        let treasury_owner = self.treasury_owner.insecure_clone();
+        self.transfer(&treasury_owner, &user.pubkey(), amount)
+            .await
+            .unwrap();
+        user
+    }
+
+    /// Transfers `amount` between the token accounts of `from` and `to`,
+    /// passing the accounts the transfer hook needs.
+    async fn transfer(
+        &mut self,
+        from: &Keypair,
+        to: &Pubkey,
+        amount: u64,
+    ) -> Result<(), BanksClientError> {
+        let mut transfer = spl_token_2022::instruction::transfer_checked(
+            &spl_token_2022::ID,
+            &pda::token_account(&from.pubkey()),
+            &pda::mint(),
+            &pda::token_account(to),
+            &from.pubkey(),
+            &[],
+            amount,
+            DECIMALS,
+        )
+        .unwrap();
+        transfer
+            .accounts
+            .extend(transfer_hook_accounts(&from.pubkey(), to));
+        self.send(&[transfer], &[from]).await
+    }
+
+    async fn stake(
+        &mut self,
+        user: &Keypair,
+        index: u32,
+        tier: u8,
+        amount: u64,
+    ) -> Result<(), BanksClientError> {
+        let stake = instructions::stake(&user.pubkey(), index, tier, amount);
+        self.send(&[stake], &[user]).await
+    }
+
+    async fn unstake(
+        &mut self,
+        user: &Keypair,
+        index: u32,
+        amount: u64,
+    ) -> Result<(), BanksClientError> {
+        let unstake =
+            instructions::unstake(&user.pubkey(), &self.treasury_owner.pubkey(), index, amount);
+        self.send(&[unstake], &[user]).await
+    }
+
+    async fn claim_rewards(&mut self, user: &Keypair, index: u32) -> Result<(), BanksClientError> {
+        let claim = instructions::claim_rewards(&user.pubkey(), index);
+        self.send(&[claim], &[user]).await
+    }
+
+    fn bridge_out_instruction(
+        &self,
+        user: &Keypair,
+        message: &Keypair,
+        amount: u64,
+        recipient: [u8; 32],
+    ) -> Instruction {
+        instructions::bridge_out(
+            &user.pubkey(),
+            &message.pubkey(),
+            &self.treasury_owner.pubkey(),
+            &wormhole::program::ID,
+            amount,
+            FOREIGN_CHAIN,
+            recipient,
+        )
+    }
+
+    /// Burns `amount` from `user`, who also pays the Wormhole fee, and
+    /// returns the message account the mock core bridge created.
+    async fn bridge_out(
+        &mut self,
+        user: &Keypair,
+        amount: u64,
+        recipient: [u8; 32],
+    ) -> Result<Pubkey, BanksClientError> {
+        let message = Keypair::new();
+        let bridge_out = self.bridge_out_instruction(user, &message, amount, recipient);
+        self.send(&[bridge_out], &[user, &message])
+            .await
+            .map(|_| message.pubkey())
+    }
+
+    /// Schedules `change` through the timelock and returns its id.
+    async fn schedule_change(
+        &mut self,
+        authority: &Keypair,
+        change: ParameterChange,
+    ) -> Result<u64, BanksClientError> {
+        let config: TokenConfig = self.account(pda::config()).await;
+        let id = config.next_change_id;
+        let schedule = instructions::schedule_change(&authority.pubkey(), &[], id, change);
+        self.send(&[schedule], &[authority]).await?;
+        Ok(id)
+    }
+
+    async fn execute_change(&mut self, id: u64) -> Result<Events, BanksClientError> {
+        let execute = instructions::execute_change(id, &self.authority());
+        self.send_with_events(&[execute], &[]).await
+    }
+
+    async fn cancel_change(
+        &mut self,
+        authority: &Keypair,
+        id: u64,
+    ) -> Result<Events, BanksClientError> {
+        let cancel = instructions::cancel_change(&authority.pubkey(), &[], id, &self.authority());
+        self.send_with_events(&[cancel], &[authority]).await
+    }
+
+    /// Schedules `change` through the timelock and executes it once the
+    /// delay has passed.
+    async fn change_parameter(&mut self, change: ParameterChange) -> Result<(), BanksClientError> {
+        let authority = self.authority_keypair();
+        let id = self.schedule_change(&authority, change).await?;
+        self.warp(TIMELOCK_DELAY).await;
+        self.execute_change(id).await.map(drop)
+    }
+
+    async fn lamports(&mut self, address: Pubkey) -> u64 {
+        self.context
+            .banks_client
+            .get_balance(address)
+            .await
+            .unwrap()
+    }
+
+    /// `bridge_in` for `vaa`, paying to the recipient its message names.
+    fn bridge_in_instruction(&self, vaa: &PostedVaaAccount) -> Instruction {
+        let (meta, message) = decode_posted_vaa(&vaa.data).unwrap();
+        instructions::bridge_in(
+            &self.authority(),
+            &wormhole::program::ID,
+            vaa.hash,
+            meta.emitter_chain,
+            meta.emitter_address,
+            meta.sequence,
+            &message,
+        )
+    }
+
+    /// Stores `vaa` as if the core bridge had verified it, then redeems it.
+    async fn bridge_in(
+        &mut self,
+        vaa: &PostedVaaAccount,
+        vaa_owner: Pubkey,
+    ) -> Result<(), BanksClientError> {
+        self.store_vaa(vaa, vaa_owner).await;
+        let bridge_in = self.bridge_in_instruction(vaa);
+        self.send(&[bridge_in], &[]).await
+    }
+
+    /// Redemption record `bridge_in` keeps for `vaa`.
+    fn claim(vaa: &PostedVaaAccount) -> Pubkey {
+        let (meta, _) = decode_posted_vaa(&vaa.data).unwrap();
+        pda::claim(meta.emitter_chain, &meta.emitter_address, meta.sequence)
+    }
+
+    async fn release_inbound(
+        &mut self,
+        caller: Option<&Keypair>,
+        vaa: &PostedVaaAccount,
+    ) -> Result<(), BanksClientError> {
+        let claim: VaaClaim = self.account(Self::claim(vaa)).await;
+        let caller_key = caller.map_or(self.authority(), Signer::pubkey);
+        let instruction = instructions::release_inbound_transfer(&caller_key, &[], &claim);
+        let signers: Vec<&Keypair> = caller.into_iter().collect();
+        self.send(&[instruction], &signers).await
+    }
+
+    async fn set_rate_limits(&mut self, outbound_limit: u64, inbound_limit: u64) {
+        let instruction = instructions::set_rate_limits(
+            &self.authority(),
+            &[],
+            FOREIGN_CHAIN,
+            outbound_limit,
+            inbound_limit,
+        );
+        self.send(&[instruction], &[]).await.unwrap();
+    }
+
+    /// Simulates `remaining_capacity` and decodes its return data.
+    async fn remaining_capacity(&mut self) -> RateLimitCapacity {
+        let instruction = instructions::remaining_capacity(FOREIGN_CHAIN);
+        let blockhash = self.context.get_new_latest_blockhash().await.unwrap();
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&self.authority()),
+            &[&self.context.payer],
+            blockhash,
+        );
+        let simulation = self
+            .context
+            .banks_client
+            .simulate_transaction(transaction)
+            .await
+            .unwrap();
+        let return_data = simulation
+            .simulation_details
+            .and_then(|details| details.return_data)
+            .expect("remaining_capacity returns data");
+        RateLimitCapacity::try_from_slice(&return_data.data).unwrap()
+    }
+
+    /// Stores the rejection `vaa` as if the core bridge had verified it,
+    /// then claims the refund of the transfer posted in `message` as
+    /// `sender`, who need not be the sender on record.
+    async fn refund_transfer(
+        &mut self,
+        sender: &Keypair,
+        vaa: &PostedVaaAccount,
+        message: Pubkey,
+    ) -> Result<(), BanksClientError> {
+        self.store_vaa(vaa, wormhole::program::ID).await;
+
+        let mut transfer: OutboundTransfer = self.account(pda::outbound_transfer(&message)).await;
+        transfer.sender = sender.pubkey();
+        let refund = instructions::refund_transfer(&wormhole::program::ID, vaa.hash, &transfer);
+        self.send(&[refund], &[sender]).await
+    }
+
+    /// Writes the posted VAA account the core bridge creates after verifying
+    /// `vaa`, owned by `owner`.
+    async fn store_vaa(&mut self, vaa: &PostedVaaAccount, owner: Pubkey) {
+        let rent = self.context.banks_client.get_rent().await.unwrap();
+        self.context.set_account(
+            &vaa.address,
+            &Account {
+                lamports: rent.minimum_balance(vaa.data.len()),
+                data: vaa.data.clone(),
+                owner,
+                ..Account::default()
+            }
+            .into(),
+        );
+    }
+
+    /// Signs a `TransferRejected` VAA from the peer chain with the guardian
+    /// quorum and returns the account the core bridge would post for it.
+    fn posted_rejection(
+        &self,
+        emitter_address: [u8; 32],
+        sequence: u64,
+        rejection: &TransferRejected,
+    ) -> PostedVaaAccount {
+        let vaa = self.guardians.sign(VaaBody::transfer_rejected(
+            FOREIGN_CHAIN,
+            emitter_address,
+            sequence,
+            rejection,
+        ));
+        assert!(vaa.verify(&self.guardians.addresses()));
+        vaa.posted_account()
+    }
+
+    /// Signs a `BridgeMessage` VAA with the guardian quorum and returns the
+    /// account the core bridge would post for it.
+    fn posted_vaa(
+        &self,
+        emitter_chain: u16,
+        emitter_address: [u8; 32],
+        sequence: u64,
+        message: &BridgeMessage,
+    ) -> PostedVaaAccount {
+        let vaa = self.guardians.sign(VaaBody::bridge_message(
+            emitter_chain,
+            emitter_address,
+            sequence,
+            message,
+        ));
+        assert!(vaa.verify(&self.guardians.addresses()));
+        vaa.posted_account()
+    }
+
+    async fn set_paused(&mut self, paused: bool) {
+        let authority = self.authority();
+        let instruction = if paused {
+            instructions::pause(&authority, &[])
+        } else {
+            instructions::unpause(&authority, &[])
+        };
+        self.send(&[instruction], &[]).await.unwrap();
+    }
+
+    /// Sends the admin instruction `build` makes for `authority` and
+    /// `cosigners`, signed by all of them.
+    async fn send_admin(
+        &mut self,
+        authority: &Keypair,
+        cosigners: &[&Keypair],
+        build: impl FnOnce(&Pubkey, &[Pubkey]) -> Instruction,
+    ) -> Result<Events, BanksClientError> {
+        let cosigner_keys: Vec<Pubkey> =
+            cosigners.iter().map(|cosigner| cosigner.pubkey()).collect();
+        let instruction = build(&authority.pubkey(), &cosigner_keys);
+        let mut signers = vec![authority];
+        signers.extend_from_slice(cosigners);
+        self.send_with_events(&[instruction], &signers).await
+    }
+
+    async fn accept_authority(
+        &mut self,
+        pending_authority: &Keypair,
+    ) -> Result<Events, BanksClientError> {
+        let instruction = instructions::accept_authority(&pending_authority.pubkey());
+        self.send_with_events(&[instruction], &[pending_authority])
+            .await
+    }
+
+    /// Claims vested tokens from the vesting account of `wallet` into it
+    /// with the `claim` builder of its allocation.
+    async fn claim_vesting(
+        &mut self,
+        beneficiary: &Keypair,
+        wallet: Pubkey,
+        claim: fn(&Pubkey, &Pubkey) -> Instruction,
+    ) -> Result<Events, BanksClientError> {
+        let instruction = claim(&beneficiary.pubkey(), &wallet);
+        self.send_with_events(&[instruction], &[beneficiary]).await
+    }
+
+    async fn vesting(&mut self, wallet: Pubkey) -> VestingInfo {
+        let user_vesting: UserVestingInfo = self.account(pda::user_vesting(&wallet)).await;
+        user_vesting.vesting_info
+    }
+
+    fn register_emitter(authority: &Pubkey, address: [u8; 32], manager: [u8; 32]) -> Instruction {
+        instructions::register_emitter(
+            authority,
+            &[],
+            FOREIGN_CHAIN,
+            address,
+            manager,
+            FOREIGN_DECIMALS,
+        )
+    }
+}
+
+fn inbound_message(recipient: &Pubkey, amount: u64) -> BridgeMessage {
+    BridgeMessage {
+        source_manager: FOREIGN_MANAGER,
+        recipient_manager: capy_solana_token::ID.to_bytes(),
+        id: [0x01; 32],
+        sender: [0x22; 32],
+        amount: TrimmedAmount::trim(amount, DECIMALS, FOREIGN_DECIMALS),
+        source_token: [0x11; 32],
+        recipient: recipient.to_bytes(),
+        recipient_chain: wormhole::CHAIN_ID_SOLANA,
+    }
+}
+
+#[tokio::test]
+async fn initialize_mints_every_allocation() {
+    let mut harness = Harness::new().await;
+
+    assert_eq!(harness.supply().await, tokens(INITIAL_SUPPLY));
+    assert_eq!(
+        harness.balance(pda::reward_vault()).await,
+        tokens(STAKING_ALLOCATION)
+    );
+
+    let config: TokenConfig = harness.account(pda::config()).await;
+    assert_eq!(config.authority, harness.authority());
+    assert_eq!(config.mint, pda::mint());
+    assert_eq!(config.treasury_wallet, harness.treasury_wallet());
+    assert!(!config.paused);
+
+    let staking_pool: StakingPool = harness.account(pda::staking_pool()).await;
+    assert_eq!(staking_pool.emission_rate, DEFAULT_EMISSION_RATE);
+    assert_eq!(staking_pool.emission_cap, tokens(STAKING_ALLOCATION));
+
+    let lock_tiers: LockTiers = harness.account(pda::lock_tiers()).await;
+    assert_eq!(lock_tiers.tiers, DEFAULT_LOCK_TIERS);
+    assert_eq!(
+        lock_tiers.early_exit_penalty_bps,
+        DEFAULT_EARLY_EXIT_PENALTY_BPS
+    );
+}
+
+#[tokio::test]
+async fn initialize_exempts_the_vaults_and_the_treasury() {
+    let mut harness = Harness::new().await;
+    for owner in [pda::vault_authority(), harness.treasury_owner.pubkey()] {
+        let exemption: Exemption = harness.account(pda::exemption(&owner)).await;
+        assert!(exemption.exempt);
+    }
+
+    // The harness grants no exemptions itself, so payouts above the limit
+    // from the treasury and the stake vault rely on the ones made here
+    let amount = MAX_TRANSFER_AMOUNT + tokens(1);
+    let user = harness.user(2 * amount).await;
+    let wallet = pda::token_account(&user.pubkey());
+    harness.stake(&user, 0, 0, amount).await.unwrap();
+    let before = harness.balance(wallet).await;
+    harness.unstake(&user, 0, amount).await.unwrap();
+    assert!(harness.balance(wallet).await >= before + amount);
+}
+
+#[tokio::test]
+async fn only_the_upgrade_authority_can_initialize() {
+    let mut harness = Harness::new().await;
+    let attacker = harness.user(tokens(1_000)).await;
+
+    let result = harness
+        .send(
+            &[instructions::initialize_mint(&attacker.pubkey())],
+            &[&attacker],
+        )
+        .await;
+    assert_error(result, TokenError::NotUpgradeAuthority.into());
+}
+
+#[tokio::test]
+async fn stake_tops_up_and_partially_unstakes() {
+    let mut harness = Harness::new().await;
+    let user = harness.user(tokens(10_000)).await;
+    let wallet = pda::token_account(&user.pubkey());
+
+    harness.stake(&user, 0, 0, tokens(2_000)).await.unwrap();
+    harness.stake(&user, 0, 0, tokens(1_000)).await.unwrap();
+
+    let user_stake: UserStakeInfo = harness.account(pda::user_stake(&user.pubkey(), 0)).await;
+    // Moving tokens into the vault is not taxed; the top-up also compounds
+    // the rewards accrued by the first stake
+    let staked = user_stake.stake_info.amount;
+    assert!(staked >= tokens(3_000));
+    assert_eq!(harness.balance(pda::stake_vault()).await, staked);
+
+    let before = harness.balance(wallet).await;
+    harness.unstake(&user, 0, tokens(1_000)).await.unwrap();
+    let user_stake: UserStakeInfo = harness.account(pda::user_stake(&user.pubkey(), 0)).await;
+    assert_eq!(user_stake.stake_info.amount, staked - tokens(1_000));
+    assert!(harness.balance(wallet).await >= before + tokens(1_000));
+
+    // Withdrawing the rest closes the position
+    harness
+        .unstake(&user, 0, user_stake.stake_info.amount)
+        .await
+        .unwrap();
+    let closed = harness
+        .context
+        .banks_client
+        .get_account(pda::user_stake(&user.pubkey(), 0))
+        .await
+        .unwrap();
+    assert!(closed.is_none());
+}
+
+#[tokio::test]
+async fn stake_below_minimum_fails() {
+    let mut harness = Harness::new().await;
+    let user = harness.user(tokens(10_000)).await;
+
+    let result = harness.stake(&user, 0, 0, tokens(999)).await;
+    assert_error(result, StakeError::BelowMinimum.into());
+}
+
+#[tokio::test]
+async fn rewards_follow_the_emission_rate() {
+    let mut harness = Harness::new().await;
+    let user = harness.user(tokens(10_000)).await;
+    let wallet = pda::token_account(&user.pubkey());
+    harness.stake(&user, 0, 0, tokens(5_000)).await.unwrap();
+
+    harness.warp(86_400).await;
+    let reward_vault_before = harness.balance(pda::reward_vault()).await;
+    let wallet_before = harness.balance(wallet).await;
+    harness.claim_rewards(&user, 0).await.unwrap();
+
+    // A single staker receives the whole emission, less rounding
+    let paid = reward_vault_before - harness.balance(pda::reward_vault()).await;
+    let expected = DEFAULT_EMISSION_RATE * 86_400;
+    assert!(paid + 1 >= expected && paid <= DEFAULT_EMISSION_RATE * (86_400 + 60));
+    assert_eq!(harness.balance(wallet).await, wallet_before + paid);
+
+    let result = harness.claim_rewards(&user, 0).await;
+    assert_error(result, StakeError::NoRewards.into());
+}
+
+#[tokio::test]
+async fn emission_stops_at_the_staking_allocation() {
+    let mut harness = Harness::new().await;
+    let user = harness.user(tokens(10_000)).await;
+    harness.stake(&user, 0, 0, tokens(5_000)).await.unwrap();
+
+    harness.warp(10 * 365 * 86_400).await;
+    harness.claim_rewards(&user, 0).await.unwrap();
+    let staking_pool: StakingPool = harness.account(pda::staking_pool()).await;
+    assert_eq!(staking_pool.emitted, tokens(STAKING_ALLOCATION));
+
+    harness.warp(365 * 86_400).await;
+    let result = harness.claim_rewards(&user, 0).await;
+    assert_error(result, StakeError::NoRewards.into());
+}
+
+#[tokio::test]
+async fn early_exit_from_a_lock_pays_the_penalty_to_treasury() {
+    let mut harness = Harness::new().await;
+    let user = harness.user(tokens(10_000)).await;
+    harness.stake(&user, 0, 1, tokens(2_000)).await.unwrap();
+
+    let user_stake: UserStakeInfo = harness.account(pda::user_stake(&user.pubkey(), 0)).await;
+    let staked = user_stake.stake_info.amount;
+    let treasury_before = harness.balance(harness.treasury_wallet()).await;
+    harness.unstake(&user, 0, staked).await.unwrap();
+
+    let penalty = staked * DEFAULT_EARLY_EXIT_PENALTY_BPS as u64 / 10_000;
+    assert_eq!(
+        harness.balance(harness.treasury_wallet()).await,
+        treasury_before + penalty
+    );
+}
+
+#[tokio::test]
+async fn team_vesting_waits_for_the_cliff_then_vests_linearly() {
+    let mut harness = Harness::new().await;
+    let team = harness.team.insecure_clone();
+    let wallet = harness.team_wallet();
+    let total = tokens(TEAM_ALLOCATION);
+
+    let result = harness
+        .claim_vesting(&team, wallet, instructions::claim_team_tokens)
+        .await;
+    assert_error(result.map(drop), VestingError::CliffNotReached.into());
+
+    // A quarter of the linear period after the cliff, give or take the
+    // seconds the bank advances between transactions
+    harness
+        .warp(TEAM_CLIFF_PERIOD + TEAM_VESTING_DURATION / 4)
+        .await;
+    let events = harness
+        .claim_vesting(&team, wallet, instructions::claim_team_tokens)
+        .await
+        .unwrap();
+    let claimed = harness.vesting(wallet).await.claimed_amount;
+    let per_second = total / TEAM_VESTING_DURATION as u64;
+    assert!(claimed >= total / 4 && claimed <= total / 4 + 60 * per_second);
+    assert_eq!(harness.balance(wallet).await, claimed);
+    let vesting_claimed: VestingClaimed = events.get();
+    assert_eq!(vesting_claimed.beneficiary, wallet);
+    assert_eq!(vesting_claimed.amount, claimed);
+    assert_eq!(vesting_claimed.total_claimed, claimed);
+
+    harness.warp(TEAM_VESTING_DURATION).await;
+    harness
+        .claim_vesting(&team, wallet, instructions::claim_team_tokens)
+        .await
+        .unwrap();
+    assert_eq!(harness.vesting(wallet).await.claimed_amount, total);
+    assert_eq!(harness.balance(pda::vesting_escrow(&wallet)).await, 0);
+
+    let result = harness
+        .claim_vesting(&team, wallet, instructions::claim_team_tokens)
+        .await;
+    assert_error(result.map(drop), VestingError::NothingToClaim.into());
+}
+
+#[tokio::test]
+async fn development_vesting_is_linear_from_the_start() {
+    let mut harness = Harness::new().await;
+    let development = harness.development.insecure_clone();
+    let wallet = harness.development_wallet();
+    let total = tokens(DEVELOPMENT_ALLOCATION);
+
+    harness.warp(DEVELOPMENT_VESTING_DURATION / 2).await;
+    harness
+        .claim_vesting(&development, wallet, instructions::claim_development_tokens)
+        .await
+        .unwrap();
+    let claimed = harness.vesting(wallet).await.claimed_amount;
+    let per_second = total / DEVELOPMENT_VESTING_DURATION as u64;
+    assert!(claimed >= total / 2 && claimed <= total / 2 + 60 * per_second);
+}
+
+#[tokio::test]
+async fn marketing_vesting_unlocks_every_quarter() {
+    let mut harness = Harness::new().await;
+    let marketing = harness.marketing.insecure_clone();
+    let wallet = harness.marketing_wallet();
+    let total = tokens(MARKETING_ALLOCATION);
+    let tranche = total / MARKETING_VESTING_QUARTERS as u64;
+
+    let result = harness
+        .claim_vesting(&marketing, wallet, instructions::claim_marketing_tokens)
+        .await;
+    assert_error(result.map(drop), VestingError::NothingToClaim.into());
+
+    // Halfway through the second quarter only the first tranche is out
+    harness.warp(MARKETING_VESTING_PERIOD * 3 / 2).await;
+    harness
+        .claim_vesting(&marketing, wallet, instructions::claim_marketing_tokens)
+        .await
+        .unwrap();
+    assert_eq!(harness.vesting(wallet).await.claimed_amount, tranche);
+    assert_eq!(harness.balance(wallet).await, tranche);
+
+    let result = harness
+        .claim_vesting(&marketing, wallet, instructions::claim_marketing_tokens)
+        .await;
+    assert_error(result.map(drop), VestingError::NothingToClaim.into());
+
+    harness.warp(MARKETING_VESTING_PERIOD).await;
+    harness
+        .claim_vesting(&marketing, wallet, instructions::claim_marketing_tokens)
+        .await
+        .unwrap();
+    assert_eq!(harness.vesting(wallet).await.claimed_amount, 2 * tranche);
+
+    harness
+        .warp(MARKETING_VESTING_PERIOD * MARKETING_VESTING_QUARTERS)
+        .await;
+    harness
+        .claim_vesting(&marketing, wallet, instructions::claim_marketing_tokens)
+        .await
+        .unwrap();
+    assert_eq!(harness.vesting(wallet).await.claimed_amount, total);
+}
+
+#[tokio::test]
+async fn vesting_is_claimed_only_by_its_beneficiary() {
+    let mut harness = Harness::new().await;
+    let attacker = harness.user(tokens(1_000)).await;
+    let team_wallet = harness.team_wallet();
+    harness
+        .warp(TEAM_CLIFF_PERIOD + TEAM_VESTING_DURATION)
+        .await;
+
+    let result = harness
+        .claim_vesting(&attacker, team_wallet, instructions::claim_team_tokens)
+        .await;
+    assert_error(result.map(drop), VestingError::Unauthorized.into());
+
+    // The marketing allocation cannot be claimed on the team schedule
+    let marketing = harness.marketing.insecure_clone();
+    let marketing_wallet = harness.marketing_wallet();
+    let result = harness
+        .claim_vesting(
+            &marketing,
+            marketing_wallet,
+            instructions::claim_team_tokens,
+        )
+        .await;
+    assert_error(result.map(drop), VestingError::WrongBeneficiary.into());
+}
+
+#[tokio::test]
+async fn hook_rejects_transfers_above_the_maximum() {
+    let mut harness = Harness::new().await;
+    let authority = harness.authority();
+    // The treasury is exempt, so the limit is checked on a regular holder
+    let sender = harness.user(2 * MAX_TRANSFER_AMOUNT).await;
+    let user = harness.user(tokens(1)).await;
+    let wallet = pda::token_account(&user.pubkey());
+
+    let result = harness
+        .transfer(&sender, &user.pubkey(), MAX_TRANSFER_AMOUNT + 1)
+        .await;
+    assert_error(result, HookError::ExceedsMaximum.into());
+
+    let before = harness.balance(wallet).await;
+    harness
+        .transfer(&sender, &user.pubkey(), MAX_TRANSFER_AMOUNT)
+        .await
+        .unwrap();
+    assert_eq!(
+        harness.balance(wallet).await,
+        before + MAX_TRANSFER_AMOUNT - transfer_fee(MAX_TRANSFER_AMOUNT)
+    );
+
+    // Only the program authority can change the limit
+    let result = harness
+        .send(
+            &[instructions::set_max_transfer_amount(
+                &user.pubkey(),
+                &[],
+                u64::MAX,
+            )],
+            &[&user],
+        )
+        .await;
+    assert_error(result, TokenError::Unauthorized.into());
+    harness
+        .send(
+            &[instructions::set_max_transfer_amount(
+                &authority,
+                &[],
+                tokens(100),
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+    let result = harness.transfer(&sender, &user.pubkey(), tokens(101)).await;
+    assert_error(result, HookError::ExceedsMaximum.into());
+    harness
+        .transfer(&sender, &user.pubkey(), tokens(100))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn exempt_owners_skip_the_transfer_limit() {
+    let mut harness = Harness::new().await;
+    let authority = harness.authority();
+    let amount = MAX_TRANSFER_AMOUNT + 1;
+    let sender = harness.user(4 * amount).await;
+    let user = harness.user(tokens(1)).await;
+
+    let result = harness
+        .send(
+            &[instructions::set_exemption(
+                &user.pubkey(),
+                &[],
+                &user.pubkey(),
+                true,
+            )],
+            &[&user],
+        )
+        .await;
+    assert_error(result, TokenError::Unauthorized.into());
+
+    // The config PDA is the hook authority, so not even the deployer can
+    // bypass the program's signer set by calling the hook directly
+    let direct = Instruction {
+        program_id: capy_solana_transfer_hook::ID,
+        accounts: capy_solana_transfer_hook::accounts::SetExemption {
+            authority,
+            payer: authority,
+            hook_config: pda::hook_config(),
+            exemption: pda::exemption(&user.pubkey()),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: capy_solana_transfer_hook::instruction::SetExemption {
+            owner: user.pubkey(),
+            exempt: true,
+        }
+        .data(),
+    };
+    let result = harness.send(&[direct], &[]).await;
+    assert_error(result, HookError::Unauthorized.into());
+
+    // Either side of the transfer being exempt lifts the limit
+    harness
+        .send(
+            &[instructions::set_exemption(
+                &authority,
+                &[],
+                &sender.pubkey(),
+                true,
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+    harness
+        .transfer(&sender, &user.pubkey(), amount)
+        .await
+        .unwrap();
+
+    harness
+        .send(
+            &[
+                instructions::set_exemption(&authority, &[], &sender.pubkey(), false),
+                instructions::set_exemption(&authority, &[], &user.pubkey(), true),
+            ],
+            &[],
+        )
+        .await
+        .unwrap();
+    harness
+        .transfer(&sender, &user.pubkey(), amount)
+        .await
+        .unwrap();
+
+    harness
+        .send(
+            &[instructions::set_exemption(
+                &authority,
+                &[],
+                &user.pubkey(),
+                false,
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+    let result = harness.transfer(&sender, &user.pubkey(), amount).await;
+    assert_error(result, HookError::ExceedsMaximum.into());
+}
+
+#[tokio::test]
+async fn bridge_out_burns_and_posts_the_message() {
+    let mut harness = Harness::new().await;
+    let user = harness.user(tokens(10_000)).await;
+    let supply_before = harness.supply().await;
+    // The core bridge creates the emitter's sequence with its first message
+    assert!(harness
+        .context
+        .banks_client
+        .get_account(pda::wormhole_sequence(&wormhole::program::ID))
+        .await
+        .unwrap()
+        .is_none());
+
+    let recipient = [0x42; 32];
+    let message = harness
+        .bridge_out(&user, tokens(1_000), recipient)
+        .await
+        .unwrap();
+    assert_eq!(harness.supply().await, supply_before - tokens(1_000));
+    assert_eq!(
+        harness
+            .lamports(pda::wormhole_fee_collector(&wormhole::program::ID))
+            .await,
+        FEE_COLLECTOR_BALANCE + WORMHOLE_FEE
+    );
+
+    let account = harness
+        .context
+        .banks_client
+        .get_account(message)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(&account.data[..3], b"msg");
+    let mut data = &account.data[3..];
+    let meta = PostedMeta::deserialize(&mut data).unwrap();
+    let payload = Vec::<u8>::deserialize(&mut data).unwrap();
+    let bridged = BridgeMessage::try_from_slice(&payload).unwrap();
+
+    assert_eq!(meta.sequence, 0);
+    assert_eq!(meta.emitter_address, pda::emitter().to_bytes());
+
+    // NTT wire format: transceiver prefix, then the transfer prefix after
+    // both manager addresses, the manager payload length, id, sender and
+    // the transfer length
+    assert_eq!(payload.len(), BridgeMessage::LEN);
+    assert_eq!(payload[..4], NTT_TRANSCEIVER_PREFIX);
+    assert_eq!(
+        payload[4 + 32 + 32 + 2 + 32 + 32 + 2..][..4],
+        NTT_TRANSFER_PREFIX
+    );
+    assert_eq!(bridged.source_manager, capy_solana_token::ID.to_bytes());
+    assert_eq!(bridged.recipient_manager, FOREIGN_MANAGER);
+    assert_eq!(bridged.id, BridgeMessage::id_from_sequence(0));
+    assert_eq!(bridged.sender, user.pubkey().to_bytes());
+    assert_eq!(
+        bridged.amount,
+        TrimmedAmount::new(1_000 * 10u64.pow(8), TRIMMED_DECIMALS)
+    );
+    assert_eq!(bridged.source_token, pda::mint().to_bytes());
+    assert_eq!(bridged.recipient_chain, FOREIGN_CHAIN);
+    assert_eq!(bridged.recipient, recipient);
+}
+
+#[tokio::test]
+async fn bridge_outs_built_before_either_lands_both_succeed() {
+    let mut harness = Harness::new().await;
+    let user = harness.user(tokens(10_000)).await;
+
+    // Both are built against the same core bridge sequence; the refund
+    // records are keyed by message account, so they do not collide
+    let (first, second) = (Keypair::new(), Keypair::new());
+    let first_out = harness.bridge_out_instruction(&user, &first, tokens(1_000), [0x42; 32]);
+    let second_out = harness.bridge_out_instruction(&user, &second, tokens(500), [0x42; 32]);
+    harness.send(&[first_out], &[&user, &first]).await.unwrap();
+    harness
+        .send(&[second_out], &[&user, &second])
+        .await
+        .unwrap();
+
+    for (message, sequence, amount) in [(&first, 0, tokens(1_000)), (&second, 1, tokens(500))] {
+        let record: OutboundTransfer = harness
+            .account(pda::outbound_transfer(&message.pubkey()))
+            .await;
+        assert_eq!(record.sequence, sequence);
+        assert_eq!(record.amount, amount);
+        let (meta, bridged) = harness
+            .context
+            .banks_client
+            .get_account(message.pubkey())
+            .await
+            .unwrap()
+            .map(|account| decode_posted_message(&account.data).unwrap())
+            .unwrap();
+        assert_eq!(meta.sequence, sequence);
+        assert_eq!(bridged.id, BridgeMessage::id_from_sequence(sequence));
+    }
+}
+
+#[tokio::test]
+async fn bridge_fee_goes_to_the_treasury() {
+    let mut harness = Harness::new().await;
+    let user = harness.user(tokens(10_000)).await;
+
+    let result = harness
+        .change_parameter(ParameterChange::BridgeFee(MAX_BRIDGE_FEE_BPS + 1))
+        .await;
+    assert_error(result, TokenError::BridgeFeeTooHigh.into());
+    harness
+        .change_parameter(ParameterChange::BridgeFee(200))
+        .await
+        .unwrap();
+
+    let treasury_before = harness.balance(harness.treasury_wallet()).await;
+    let supply_before = harness.supply().await;
+    let message = harness
+        .bridge_out(&user, tokens(1_000), [0x42; 32])
+        .await
+        .unwrap();
+
+    // 2% goes to the treasury, which pays the transfer tax on it; only the
+    // rest is burned and bridged
+    let fee = tokens(20);
+    assert_eq!(
+        harness.balance(harness.treasury_wallet()).await,
+        treasury_before + fee - transfer_fee(fee)
+    );
+    assert_eq!(harness.supply().await, supply_before - tokens(980));
+    let account = harness
+        .context
+        .banks_client
+        .get_account(message)
+        .await
+        .unwrap()
+        .unwrap();
+    let mut data = &account.data[3..];
+    PostedMeta::deserialize(&mut data).unwrap();
+    let payload = Vec::<u8>::deserialize(&mut data).unwrap();
+    let bridged = BridgeMessage::try_from_slice(&payload).unwrap();
+    assert_eq!(
+        bridged.amount,
+        TrimmedAmount::new(980 * 10u64.pow(8), TRIMMED_DECIMALS)
+    );
+}
+
+#[tokio::test]
+async fn bridge_out_rejects_a_wormhole_fee_above_the_configured_maximum() {
+    let mut harness = Harness::new().await;
+    let user = harness.user(tokens(10_000)).await;
+
+    let mut bridge = harness
+        .context
+        .banks_client
+        .get_account(pda::wormhole_bridge(&wormhole::program::ID))
+        .await
+        .unwrap()
+        .unwrap();
+    bridge.data[16..24].copy_from_slice(&(WORMHOLE_FEE + 1).to_le_bytes());
+    harness.context.set_account(
+        &pda::wormhole_bridge(&wormhole::program::ID),
+        &bridge.into(),
+    );
+
+    let result = harness
+        .bridge_out(&user, tokens(1_000), [0x42; 32])
+        .await
+        .map(|_| ());
+    assert_error(result, TokenError::WormholeFeeTooHigh.into());
+}
+
+#[tokio::test]
+async fn bridge_out_uses_only_the_configured_core_bridge() {
+    let mut harness = Harness::new().await;
+    let authority = harness.authority_keypair();
+    let user = harness.user(tokens(10_000)).await;
+
+    let config: TokenConfig = harness.account(pda::config()).await;
+    let mut wormhole_config = config.wormhole_config.clone();
+    wormhole_config.bridge = Pubkey::new_unique();
+    harness
+        .send_admin(&authority, &[], |authority, cosigners| {
+            instructions::set_wormhole_config(authority, cosigners, wormhole_config)
+        })
+        .await
+        .unwrap();
+
+    let result = harness
+        .bridge_out(&user, tokens(1_000), [0x42; 32])
+        .await
+        .map(|_| ());
+    assert_error(result, TokenError::WrongWormholeBridge.into());
+}
+
+#[tokio::test]
+async fn bridge_in_mints_a_verified_vaa_once() {
+    let mut harness = Harness::new().await;
+    let user = harness.user(tokens(10_000)).await;
+    let wallet = pda::token_account(&user.pubkey());
+    let before = harness.balance(wallet).await;
+
+    let vaa = harness.posted_vaa(
+        FOREIGN_CHAIN,
+        FOREIGN_EMITTER,
+        7,
+        &inbound_message(&user.pubkey(), tokens(500)),
+    );
+    harness
+        .bridge_in(&vaa, wormhole::program::ID)
+        .await
+        .unwrap();
+    assert_eq!(harness.balance(wallet).await, before + tokens(500));
+
+    let replay = harness.bridge_in(&vaa, wormhole::program::ID).await;
+    assert_error(replay, TokenError::AlreadyRedeemed.into());
+    assert_eq!(harness.balance(wallet).await, before + tokens(500));
+}
+
+#[tokio::test]
+async fn bridge_in_rejects_a_second_vaa_for_the_same_message() {
+    let mut harness = Harness::new().await;
+    let user = harness.user(tokens(10_000)).await;
+    let attacker = harness.user(tokens(10_000)).await;
+    let attacker_wallet = pda::token_account(&attacker.pubkey());
+
+    let vaa = harness.posted_vaa(
+        FOREIGN_CHAIN,
+        FOREIGN_EMITTER,
+        7,
+        &inbound_message(&user.pubkey(), tokens(500)),
+    );
+    harness
+        .bridge_in(&vaa, wormhole::program::ID)
+        .await
+        .unwrap();
+    let supply = harness.supply().await;
+    let attacker_before = harness.balance(attacker_wallet).await;
+
+    // A different VAA (and posted account) for the same emitter and
+    // sequence is still the same transfer
+    let double_spend = harness.posted_vaa(
+        FOREIGN_CHAIN,
+        FOREIGN_EMITTER,
+        7,
+        &inbound_message(&attacker.pubkey(), tokens(5_000)),
+    );
+    assert_ne!(double_spend.hash, vaa.hash);
+    let result = harness
+        .bridge_in(&double_spend, wormhole::program::ID)
+        .await;
+    assert_error(result, TokenError::AlreadyRedeemed.into());
+    assert_eq!(harness.balance(attacker_wallet).await, attacker_before);
+    assert_eq!(harness.supply().await, supply);
+}
+
+#[tokio::test]
+async fn bridge_in_rejects_invalid_vaas() {
+    let mut harness = Harness::new().await;
+    let user = harness.user(tokens(10_000)).await;
+    let message = inbound_message(&user.pubkey(), tokens(500));
+
+    // Not written by the core bridge
+    let forged = harness.posted_vaa(FOREIGN_CHAIN, FOREIGN_EMITTER, 1, &message);
+    let result = harness.bridge_in(&forged, system_program::ID).await;
+    assert_error(
+        result,
+        anchor_lang::error::ErrorCode::AccountOwnedByWrongProgram.into(),
+    );
+
+    let unknown_emitter = harness.posted_vaa(FOREIGN_CHAIN, [0xab; 32], 2, &message);
+    let result = harness
+        .bridge_in(&unknown_emitter, wormhole::program::ID)
+        .await;
+    assert_error(result, TokenError::UnknownEmitter.into());
+
+    let mut wrong_chain_message = message.clone();
+    wrong_chain_message.recipient_chain = FOREIGN_CHAIN;
+    let wrong_chain = harness.posted_vaa(FOREIGN_CHAIN, FOREIGN_EMITTER, 3, &wrong_chain_message);
+    let result = harness.bridge_in(&wrong_chain, wormhole::program::ID).await;
+    assert_error(result, TokenError::WrongTargetChain.into());
+
+    let mut wrong_manager_message = message.clone();
+    wrong_manager_message.source_manager = [0xab; 32];
+    let wrong_manager =
+        harness.posted_vaa(FOREIGN_CHAIN, FOREIGN_EMITTER, 5, &wrong_manager_message);
+    let result = harness
+        .bridge_in(&wrong_manager, wormhole::program::ID)
+        .await;
+    assert_error(result, TokenError::UnknownPeerManager.into());
+
+    let mut other_recipient_manager = message.clone();
+    other_recipient_manager.recipient_manager = [0xab; 32];
+    let misaddressed =
+        harness.posted_vaa(FOREIGN_CHAIN, FOREIGN_EMITTER, 6, &other_recipient_manager);
+    let result = harness
+        .bridge_in(&misaddressed, wormhole::program::ID)
+        .await;
+    assert_error(result, TokenError::WrongRecipientManager.into());
+
+    // The client pays the recipient the message names; redirect it
+    let other = harness.user(tokens(10)).await;
+    let wrong_recipient = harness.posted_vaa(FOREIGN_CHAIN, FOREIGN_EMITTER, 4, &message);
+    harness
+        .store_vaa(&wrong_recipient, wormhole::program::ID)
+        .await;
+    let mut bridge_in = harness.bridge_in_instruction(&wrong_recipient);
+    bridge_in.accounts[1].pubkey = pda::token_account(&other.pubkey());
+    let result = harness.send(&[bridge_in], &[]).await;
+    assert_error(result, TokenError::RecipientMismatch.into());
+}
+
+#[tokio::test]
+async fn bridge_amounts_travel_in_eight_decimals() {
+    let mut harness = Harness::new().await;
+    let user = harness.user(tokens(10_000)).await;
+    let wallet = pda::token_account(&user.pubkey());
+
+    // The last digit does not fit in 8 decimals and stays in the wallet
+    let balance_before = harness.balance(wallet).await;
+    let supply_before = harness.supply().await;
+    let message = harness
+        .bridge_out(&user, tokens(1) + 7, [0x42; 32])
+        .await
+        .unwrap();
+    assert_eq!(harness.balance(wallet).await, balance_before - tokens(1));
+    assert_eq!(harness.supply().await, supply_before - tokens(1));
+    let account = harness
+        .context
+        .banks_client
+        .get_account(message)
+        .await
+        .unwrap()
+        .unwrap();
+    let mut data = &account.data[3..];
+    PostedMeta::deserialize(&mut data).unwrap();
+    let payload = Vec::<u8>::deserialize(&mut data).unwrap();
+    let bridged = BridgeMessage::try_from_slice(&payload).unwrap();
+    assert_eq!(
+        bridged.amount,
+        TrimmedAmount::new(10u64.pow(8), TRIMMED_DECIMALS)
+    );
+
+    // Dust alone cannot be bridged
+    let result = harness.bridge_out(&user, 7, [0x42; 32]).await.map(|_| ());
+    assert_error(result, TokenError::ZeroAmount.into());
+
+    // Inbound amounts are scaled up from the decimals they carry
+    let mut message = inbound_message(&user.pubkey(), 0);
+    message.amount = TrimmedAmount::new(1_500_000, 6);
+    let vaa = harness.posted_vaa(FOREIGN_CHAIN, FOREIGN_EMITTER, 1, &message);
+    let balance_before = harness.balance(wallet).await;
+    harness
+        .bridge_in(&vaa, wormhole::program::ID)
+        .await
+        .unwrap();
+    assert_eq!(
+        harness.balance(wallet).await,
+        balance_before + tokens(3) / 2
+    );
+
+    message.amount = TrimmedAmount::new(tokens(1), DECIMALS);
+    let vaa = harness.posted_vaa(FOREIGN_CHAIN, FOREIGN_EMITTER, 2, &message);
+    let result = harness.bridge_in(&vaa, wormhole::program::ID).await;
+    assert_error(result, TokenError::InvalidAmountDecimals.into());
+
+    message.amount = TrimmedAmount::new(u64::MAX, 0);
+    let vaa = harness.posted_vaa(FOREIGN_CHAIN, FOREIGN_EMITTER, 3, &message);
+    let result = harness.bridge_in(&vaa, wormhole::program::ID).await;
+    assert_error(result, TokenError::AmountOverflow.into());
+}
+
+#[tokio::test]
+async fn bridge_out_is_limited_by_the_outbound_capacity() {
+    let mut harness = Harness::new().await;
+    let user = harness.user(tokens(10_000)).await;
+    harness.set_rate_limits(tokens(1_500), tokens(2_000)).await;
+
+    harness
+        .bridge_out(&user, tokens(1_000), [0x42; 32])
+        .await
+        .unwrap();
+    let capacity = harness.remaining_capacity().await;
+    assert_eq!(capacity.outbound, tokens(500));
+
+    let result = harness
+        .bridge_out(&user, tokens(1_000), [0x42; 32])
+        .await
+        .map(|_| ());
+    assert_error(result, TokenError::RateLimitExceeded.into());
+
+    // Half a window refills half the limit
+    harness.warp(RATE_LIMIT_DURATION / 2).await;
+    assert_eq!(harness.remaining_capacity().await.outbound, tokens(1_250));
+    harness
+        .bridge_out(&user, tokens(1_000), [0x42; 32])
+        .await
+        .unwrap();
+
+    // Inbound transfers hand their amount back to the outbound capacity
+    let vaa = harness.posted_vaa(
+        FOREIGN_CHAIN,
+        FOREIGN_EMITTER,
+        1,
+        &inbound_message(&user.pubkey(), tokens(200)),
+    );
+    harness
+        .bridge_in(&vaa, wormhole::program::ID)
+        .await
+        .unwrap();
+    let capacity = harness.remaining_capacity().await;
+    assert_eq!(capacity.outbound, tokens(450));
+    assert_eq!(capacity.inbound, tokens(1_800));
+}
+
+#[tokio::test]
+async fn inbound_transfers_above_capacity_are_queued() {
+    let mut harness = Harness::new().await;
+    let user = harness.user(tokens(10_000)).await;
+    let wallet = pda::token_account(&user.pubkey());
+    let before = harness.balance(wallet).await;
+    harness.set_rate_limits(tokens(1_000), tokens(100)).await;
+
+    let vaa = harness.posted_vaa(
+        FOREIGN_CHAIN,
+        FOREIGN_EMITTER,
+        1,
+        &inbound_message(&user.pubkey(), tokens(500)),
+    );
+    harness
+        .bridge_in(&vaa, wormhole::program::ID)
+        .await
+        .unwrap();
+    assert_eq!(harness.balance(wallet).await, before);
+    let claim: VaaClaim = harness.account(Harness::claim(&vaa)).await;
+    assert!(claim.is_queued());
+    assert_eq!(claim.amount, tokens(500));
+    let peer: ForeignEmitter = harness.account(pda::foreign_emitter(FOREIGN_CHAIN)).await;
+    assert_eq!(peer.inbound.capacity_at_last_tx, tokens(100));
+
+    let replay = harness.bridge_in(&vaa, wormhole::program::ID).await;
+    assert_error(replay, TokenError::AlreadyRedeemed.into());
+
+    // Only the authority can release before the delay
+    let result = harness.release_inbound(Some(&user), &vaa).await;
+    assert_error(result, TokenError::Unauthorized.into());
+
+    harness.warp(INBOUND_QUEUE_DELAY).await;
+    harness.release_inbound(Some(&user), &vaa).await.unwrap();
+    assert_eq!(harness.balance(wallet).await, before + tokens(500));
+    let result = harness.release_inbound(Some(&user), &vaa).await;
+    assert_error(result, TokenError::NotQueued.into());
+
+    let early = harness.posted_vaa(
+        FOREIGN_CHAIN,
+        FOREIGN_EMITTER,
+        2,
+        &inbound_message(&user.pubkey(), tokens(300)),
+    );
+    harness
+        .bridge_in(&early, wormhole::program::ID)
+        .await
+        .unwrap();
+    harness.release_inbound(None, &early).await.unwrap();
+    assert_eq!(harness.balance(wallet).await, before + tokens(800));
+}
+
+#[tokio::test]
+async fn rejected_transfers_are_refunded_to_the_sender_once() {
+    let mut harness = Harness::new().await;
+    let user = harness.user(tokens(10_000)).await;
+    let wallet = pda::token_account(&user.pubkey());
+    let first = harness
+        .bridge_out(&user, tokens(1_000), [0x42; 32])
+        .await
+        .unwrap();
+    let second = harness
+        .bridge_out(&user, tokens(500), [0x42; 32])
+        .await
+        .unwrap();
+    let balance_before = harness.balance(wallet).await;
+    let supply_before = harness.supply().await;
+
+    let rejection = TransferRejected {
+        source_manager: FOREIGN_MANAGER,
+        recipient_manager: capy_solana_token::ID.to_bytes(),
+        id: BridgeMessage::id_from_sequence(0),
+        reason: 1,
+    };
+
+    // Only the peer the transfer was sent to can reject it
+    let unknown_emitter = harness.posted_rejection([0xab; 32], 1, &rejection);
+    let result = harness
+        .refund_transfer(&user, &unknown_emitter, first)
+        .await;
+    assert_error(result, TokenError::UnknownEmitter.into());
+
+    // The rejection only refunds the transfer it names
+    let vaa = harness.posted_rejection(FOREIGN_EMITTER, 2, &rejection);
+    let result = harness.refund_transfer(&user, &vaa, second).await;
+    assert_error(result, TokenError::RejectionMismatch.into());
+
+    let other = harness.user(tokens(10)).await;
+    let result = harness.refund_transfer(&other, &vaa, first).await;
+    assert_error(
+        result,
+        anchor_lang::error::ErrorCode::ConstraintHasOne.into(),
+    );
+
+    harness.refund_transfer(&user, &vaa, first).await.unwrap();
+    assert_eq!(
+        harness.balance(wallet).await,
+        balance_before + tokens(1_000)
+    );
+    assert_eq!(harness.supply().await, supply_before + tokens(1_000));
+    let record: OutboundTransfer = harness.account(pda::outbound_transfer(&first)).await;
+    assert_eq!(record.sender, user.pubkey());
+    assert_eq!(record.amount, tokens(1_000));
+    assert_eq!(record.sequence, 0);
+    assert_eq!(record.message, first);
+    assert!(record.refunded);
+
+    let replay = harness.refund_transfer(&user, &vaa, first).await;
+    assert_error(replay, TokenError::AlreadyRefunded.into());
+}
+
+#[tokio::test]
+async fn admin_parameters_are_bounded() {
+    let mut harness = Harness::new().await;
+    let authority = harness.authority_keypair();
+
+    let result = harness
+        .send_admin(&authority, &[], |authority, cosigners| {
+            instructions::set_min_stake_amount(authority, cosigners, 0)
+        })
+        .await;
+    assert_error(result.map(drop), TokenError::ZeroAmount.into());
+
+    let config: TokenConfig = harness.account(pda::config()).await;
+    let mut wormhole_config = config.wormhole_config.clone();
+    wormhole_config.bridge = Pubkey::default();
+    let result = harness
+        .send_admin(&authority, &[], |authority, cosigners| {
+            instructions::set_wormhole_config(authority, cosigners, wormhole_config)
+        })
+        .await;
+    assert_error(result.map(drop), TokenError::InvalidWormholeConfig.into());
+
+    // The consistency level only changes through the timelock
+    let mut wormhole_config = config.wormhole_config.clone();
+    wormhole_config.consistency_level = 0;
+    let result = harness
+        .send_admin(&authority, &[], |authority, cosigners| {
+            instructions::set_wormhole_config(authority, cosigners, wormhole_config)
+        })
+        .await;
+    assert_error(result.map(drop), TokenError::TimelockRequired.into());
+
+    let out_of_bounds = [
+        (
+            ParameterChange::TransferTaxRate(MAX_TRANSFER_TAX_RATE + 1),
+            TokenError::TaxRateTooHigh.into(),
+        ),
+        (
+            ParameterChange::EmissionRate(MAX_EMISSION_RATE + 1),
+            StakeError::EmissionRateTooHigh.into(),
+        ),
+        (
+            ParameterChange::ConsistencyLevel(2),
+            TokenError::InvalidWormholeConfig.into(),
+        ),
+        (
+            ParameterChange::BridgeFee(MAX_BRIDGE_FEE_BPS + 1),
+            TokenError::BridgeFeeTooHigh.into(),
+        ),
+    ];
+    for (change, code) in out_of_bounds {
+        let result = harness.schedule_change(&authority, change).await;
+        assert_error(result.map(drop), code);
+    }
+}
+
+#[tokio::test]
+async fn admin_updates_emit_config_updated() {
+    let mut harness = Harness::new().await;
+    let authority = harness.authority_keypair();
+
+    let stranger = harness.user(tokens(1_000)).await;
+    let result = harness
+        .send_admin(&stranger, &[], |authority, cosigners| {
+            instructions::set_min_stake_amount(authority, cosigners, 1)
+        })
+        .await;
+    assert_error(result.map(drop), TokenError::Unauthorized.into());
+
+    let events = harness
+        .send_admin(&authority, &[], |authority, cosigners| {
+            instructions::set_min_stake_amount(authority, cosigners, tokens(50))
+        })
+        .await
+        .unwrap();
+    let updated: ConfigUpdated = events.get();
+    assert_eq!(updated.parameter, ConfigParameter::MinStakeAmount);
+    assert_eq!(updated.authority, authority.pubkey());
+    assert_eq!(updated.value, ParameterValue::MinStakeAmount(tokens(50)));
+    let config: TokenConfig = harness.account(pda::config()).await;
+    assert_eq!(config.min_stake_amount, tokens(50));
+
+    let id = harness
+        .schedule_change(&authority, ParameterChange::ConsistencyLevel(0))
+        .await
+        .unwrap();
+    harness.warp(TIMELOCK_DELAY).await;
+    let events = harness.execute_change(id).await.unwrap();
+    assert_eq!(events.get::<ChangeExecuted>().id, 0);
+    let updated: ConfigUpdated = events.get();
+    assert_eq!(updated.parameter, ConfigParameter::ConsistencyLevel);
+    assert_eq!(updated.authority, authority.pubkey());
+    assert_eq!(updated.value, ParameterValue::ConsistencyLevel(0));
+
+    // Re-registering a peer updates it in place
+    let manager = [0x77; 32];
+    let events = harness
+        .send_with_events(
+            &[Harness::register_emitter(
+                &authority.pubkey(),
+                FOREIGN_EMITTER,
+                manager,
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+    let registered: PeerRegistered = events.get();
+    assert_eq!(registered.chain, FOREIGN_CHAIN);
+    assert_eq!(registered.address, FOREIGN_EMITTER);
+    assert_eq!(registered.manager, manager);
+    assert_eq!(registered.token_decimals, FOREIGN_DECIMALS);
+}
+
+#[tokio::test]
+async fn collected_fees_go_to_the_treasury() {
+    let mut harness = Harness::new().await;
+    let user = harness.user(tokens(10_000)).await;
+    let treasury_before = harness.balance(harness.treasury_wallet()).await;
+
+    let collect = instructions::collect_transfer_fees(
+        &harness.treasury_wallet(),
+        &[pda::token_account(&user.pubkey())],
+    );
+    let events = harness.send_with_events(&[collect], &[]).await.unwrap();
+
+    let fee = transfer_fee(tokens(10_000));
+    assert_eq!(
+        harness.balance(harness.treasury_wallet()).await,
+        treasury_before + fee
+    );
+    let collected: FeesCollected = events.get();
+    assert_eq!(collected.treasury_wallet, harness.treasury_wallet());
+    assert_eq!(collected.amount, fee);
+}
+
+#[tokio::test]
+async fn authority_moves_only_when_the_proposed_key_accepts() {
+    let mut harness = Harness::new().await;
+    let authority = harness.authority_keypair();
+    let new_authority = harness.user(tokens(1_000)).await;
+    let stranger = harness.user(tokens(1_000)).await;
+
+    let events = harness
+        .send_admin(&authority, &[], |authority, cosigners| {
+            instructions::propose_authority(authority, cosigners, new_authority.pubkey())
+        })
+        .await
+        .unwrap();
+    let proposed: AuthorityProposed = events.get();
+    assert_eq!(proposed.pending_authority, new_authority.pubkey());
+
+    // Proposing alone changes nothing
+    let config: TokenConfig = harness.account(pda::config()).await;
+    assert_eq!(config.authority, authority.pubkey());
+    assert_eq!(config.pending_authority, new_authority.pubkey());
+
+    let result = harness.accept_authority(&stranger).await;
+    assert_error(result.map(drop), TokenError::NotPendingAuthority.into());
+
+    let events = harness.accept_authority(&new_authority).await.unwrap();
+    let transferred: AuthorityTransferred = events.get();
+    assert_eq!(transferred.previous_authority, authority.pubkey());
+    assert_eq!(transferred.new_authority, new_authority.pubkey());
+    let config: TokenConfig = harness.account(pda::config()).await;
+    assert_eq!(config.authority, new_authority.pubkey());
+    assert_eq!(config.pending_authority, Pubkey::default());
+
+    let result = harness
+        .send_admin(&authority, &[], |authority, cosigners| {
+            instructions::set_min_stake_amount(authority, cosigners, 1)
+        })
+        .await;
+    assert_error(result.map(drop), TokenError::Unauthorized.into());
+    harness
+        .send_admin(&new_authority, &[], |authority, cosigners| {
+            instructions::set_min_stake_amount(authority, cosigners, 1)
+        })
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn multisig_authority_needs_the_threshold_of_signers() {
+    let mut harness = Harness::new().await;
+    let authority = harness.authority_keypair();
+    let (first, second, outsider) = (Keypair::new(), Keypair::new(), Keypair::new());
+
+    let result = harness
+        .send_admin(&authority, &[], |authority, cosigners| {
+            instructions::set_authority_signers(authority, cosigners, vec![first.pubkey()], 2)
+        })
+        .await;
+    assert_error(result.map(drop), TokenError::InvalidThreshold.into());
+
+    let signers = vec![authority.pubkey(), first.pubkey(), second.pubkey()];
+    let events = harness
+        .send_admin(&authority, &[], |authority, cosigners| {
+            instructions::set_authority_signers(authority, cosigners, signers.clone(), 2)
+        })
+        .await
+        .unwrap();
+    let updated: AuthoritySignersUpdated = events.get();
+    assert_eq!(updated.signers, signers);
+    assert_eq!(updated.threshold, 2);
+
+    // One approval, or approvals from keys outside the set, are not enough
+    let result = harness
+        .send_admin(&authority, &[], |authority, cosigners| {
+            instructions::set_min_stake_amount(authority, cosigners, 1)
+        })
+        .await;
+    assert_error(result.map(drop), TokenError::NotEnoughSigners.into());
+    let result = harness
+        .send_admin(&authority, &[&outsider], |authority, cosigners| {
+            instructions::set_min_stake_amount(authority, cosigners, 1)
+        })
+        .await;
+    assert_error(result.map(drop), TokenError::NotEnoughSigners.into());
+
+    harness
+        .send_admin(&authority, &[&first], |authority, cosigners| {
+            instructions::set_min_stake_amount(authority, cosigners, 2)
+        })
+        .await
+        .unwrap();
+    harness
+        .send_admin(&first, &[&second], |authority, cosigners| {
+            instructions::set_min_stake_amount(authority, cosigners, 3)
+        })
+        .await
+        .unwrap();
+    let config: TokenConfig = harness.account(pda::config()).await;
+    assert_eq!(config.min_stake_amount, 3);
+}
+
+#[tokio::test]
+async fn timelocked_changes_wait_for_their_eta() {
+    let mut harness = Harness::new().await;
+    let authority = harness.authority_keypair();
+
+    let id = harness
+        .schedule_change(&authority, ParameterChange::ConsistencyLevel(0))
+        .await
+        .unwrap();
+    let result = harness.execute_change(id).await;
+    assert_error(result.map(drop), TokenError::TimelockNotExpired.into());
+
+    harness.warp(TIMELOCK_DELAY - 3_600).await;
+    let result = harness.execute_change(id).await;
+    assert_error(result.map(drop), TokenError::TimelockNotExpired.into());
+    let config: TokenConfig = harness.account(pda::config()).await;
+    assert_eq!(config.wormhole_config.consistency_level, 1);
+
+    harness.warp(3_600).await;
+    harness.execute_change(id).await.unwrap();
+    let config: TokenConfig = harness.account(pda::config()).await;
+    assert_eq!(config.wormhole_config.consistency_level, 0);
+    assert!(harness
+        .context
+        .banks_client
+        .get_account(pda::pending_change(id))
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn a_new_tax_rate_applies_two_epochs_after_it_is_executed() {
+    let mut harness = Harness::new().await;
+    let treasury_owner = harness.treasury_owner.insecure_clone();
+    let user = harness.user(tokens(1)).await;
+    let wallet = pda::token_account(&user.pubkey());
+
+    harness
+        .change_parameter(ParameterChange::TransferTaxRate(5))
+        .await
+        .unwrap();
+    let mut clock: Clock = harness.context.banks_client.get_sysvar().await.unwrap();
+    let config: TokenConfig = harness.account(pda::config()).await;
+    assert_eq!(config.transfer_tax_rate, 5);
+    assert_eq!(config.transfer_tax_rate_epoch, clock.epoch + 2);
+
+    // Until then transfers still pay the old rate
+    let before = harness.balance(wallet).await;
+    harness
+        .transfer(&treasury_owner, &user.pubkey(), tokens(100))
+        .await
+        .unwrap();
+    assert_eq!(
+        harness.balance(wallet).await,
+        before + tokens(100) - transfer_fee(tokens(100))
+    );
+
+    clock.epoch = config.transfer_tax_rate_epoch;
+    harness.context.set_sysvar(&clock);
+    let before = harness.balance(wallet).await;
+    harness
+        .transfer(&treasury_owner, &user.pubkey(), tokens(100))
+        .await
+        .unwrap();
+    assert_eq!(harness.balance(wallet).await, before + tokens(95));
+}
+
+#[tokio::test]
+async fn cancelled_changes_cannot_be_executed() {
+    let mut harness = Harness::new().await;
+    let authority = harness.authority_keypair();
+    let stranger = harness.user(tokens(1_000)).await;
+
+    let id = harness
+        .schedule_change(&authority, ParameterChange::ConsistencyLevel(0))
+        .await
+        .unwrap();
+    let result = harness.cancel_change(&stranger, id).await;
+    assert_error(result.map(drop), TokenError::Unauthorized.into());
+
+    let events = harness.cancel_change(&authority, id).await.unwrap();
+    assert_eq!(events.get::<ChangeCancelled>().id, 0);
+
+    harness.warp(TIMELOCK_DELAY).await;
+    let result = harness.execute_change(id).await;
+    assert_error(
+        result.map(drop),
+        anchor_lang::error::ErrorCode::AccountNotInitialized.into(),
+    );
+    let config: TokenConfig = harness.account(pda::config()).await;
+    assert_eq!(config.wormhole_config.consistency_level, 1);
+}
+
+#[tokio::test]
+async fn pause_blocks_staking_and_bridging() {
+    let mut harness = Harness::new().await;
+    let user = harness.user(tokens(10_000)).await;
+    harness.set_paused(true).await;
+
+    let result = harness.stake(&user, 0, 0, tokens(2_000)).await;
+    assert_error(result, TokenError::Paused.into());
+    let result = harness
+        .bridge_out(&user, tokens(1_000), [0x42; 32])
+        .await
+        .map(|_| ());
+    assert_error(result, TokenError::Paused.into());
+    let vaa = harness.posted_vaa(
+        FOREIGN_CHAIN,
+        FOREIGN_EMITTER,
+        9,
+        &inbound_message(&user.pubkey(), tokens(500)),
+    );
+    let result = harness.bridge_in(&vaa, wormhole::program::ID).await;
+    assert_error(result, TokenError::Paused.into());
+
+    harness.set_paused(false).await;
+    harness.stake(&user, 0, 0, tokens(2_000)).await.unwrap();
+}
+
+#[test]
+fn mock_guardians_sign_wire_format_vaas() {
+    let guardians = MockGuardianSet::new(0, 19);
+    let body = VaaBody::bridge_message(
+        FOREIGN_CHAIN,
+        FOREIGN_EMITTER,
+        1,
+        &inbound_message(&Pubkey::new_unique(), tokens(1)),
+    );
+
+    let vaa = guardians.sign(body.clone());
+    let parsed = SignedVaa::parse(&vaa.serialize()).unwrap();
+    assert_eq!(parsed, vaa);
+    assert_eq!(parsed.signatures.len(), 13);
+    assert!(parsed.verify(&guardians.addresses()));
+
+    let below_quorum = guardians.sign_with(body.clone(), &[0, 1, 2]);
+    assert!(!below_quorum.verify(&guardians.addresses()));
+    let other_set = MockGuardianSet::from_secret_keys(1, Vec::new());
+    assert!(!vaa.verify(&other_set.addresses()));
+}
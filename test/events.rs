@@ -0,0 +1,161 @@
+//! Anchor event capture for the natively run programs.
+//!
+//! `emit!` writes events with `sol_log_data`, which solana-program-test's
+//! syscall stubs silently drop for programs added with `processor!`.
+//! `capture` wraps those stubs so every event is logged as
+//! `Program log: event: <base64>`, and `Events` decodes those lines back
+//! from a transaction's log messages.
+
+use anchor_lang::Event;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use solana_program_test::ProgramTest;
+use solana_sdk::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::Instruction,
+    program_stubs::{set_syscall_stubs, SyscallStubs},
+    pubkey::Pubkey,
+};
+use std::sync::Once;
+
+const EVENT_LOG_PREFIX: &str = "Program log: event: ";
+
+/// Installs the event-logging stubs. Must run before any bank is started:
+/// swapping stubs while another test is inside a CPI can deadlock on the
+/// stubs lock.
+pub fn capture() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        // solana-program-test installs its own stubs when its first bank
+        // starts, so start a throwaway one on a separate runtime first.
+        std::thread::spawn(|| {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(ProgramTest::default().start());
+        })
+        .join()
+        .unwrap();
+        let program_test_stubs = set_syscall_stubs(Box::new(NoStubs));
+        set_syscall_stubs(Box::new(EventLogStubs(program_test_stubs)));
+    });
+}
+
+/// Anchor events a transaction emitted, decoded from its logs.
+pub struct Events(Vec<Vec<u8>>);
+
+impl Events {
+    pub fn from_logs(logs: &[String]) -> Self {
+        Self(
+            logs.iter()
+                .filter_map(|log| log.strip_prefix(EVENT_LOG_PREFIX))
+                .map(|data| BASE64.decode(data).unwrap())
+                .collect(),
+        )
+    }
+
+    /// First event of type `T`; panics if none was emitted.
+    pub fn get<T: Event>(&self) -> T {
+        self.0
+            .iter()
+            .find(|data| data.starts_with(&T::DISCRIMINATOR))
+            .map(|data| T::try_from_slice(&data[8..]).unwrap())
+            .expect("event was not emitted")
+    }
+}
+
+/// Placeholder while the solana-program-test stubs are swapped out.
+struct NoStubs;
+
+impl SyscallStubs for NoStubs {}
+
+/// Delegates to the solana-program-test stubs, logging `sol_log_data`
+/// fields instead of dropping them.
+struct EventLogStubs(Box<dyn SyscallStubs>);
+
+impl SyscallStubs for EventLogStubs {
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        for field in fields {
+            self.0.sol_log(&format!("event: {}", BASE64.encode(field)));
+        }
+    }
+
+    fn sol_log(&self, message: &str) {
+        self.0.sol_log(message)
+    }
+
+    fn sol_log_compute_units(&self) {
+        self.0.sol_log_compute_units()
+    }
+
+    fn sol_remaining_compute_units(&self) -> u64 {
+        self.0.sol_remaining_compute_units()
+    }
+
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        self.0
+            .sol_invoke_signed(instruction, account_infos, signers_seeds)
+    }
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_clock_sysvar(var_addr)
+    }
+
+    fn sol_get_epoch_schedule_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_epoch_schedule_sysvar(var_addr)
+    }
+
+    fn sol_get_fees_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_fees_sysvar(var_addr)
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_rent_sysvar(var_addr)
+    }
+
+    fn sol_get_epoch_rewards_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_epoch_rewards_sysvar(var_addr)
+    }
+
+    fn sol_get_last_restart_slot(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_last_restart_slot(var_addr)
+    }
+
+    unsafe fn sol_memcpy(&self, dst: *mut u8, src: *const u8, n: usize) {
+        self.0.sol_memcpy(dst, src, n)
+    }
+
+    unsafe fn sol_memmove(&self, dst: *mut u8, src: *const u8, n: usize) {
+        self.0.sol_memmove(dst, src, n)
+    }
+
+    unsafe fn sol_memcmp(&self, s1: *const u8, s2: *const u8, n: usize, result: *mut i32) {
+        self.0.sol_memcmp(s1, s2, n, result)
+    }
+
+    unsafe fn sol_memset(&self, s: *mut u8, c: u8, n: usize) {
+        self.0.sol_memset(s, c, n)
+    }
+
+    fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        self.0.sol_get_return_data()
+    }
+
+    fn sol_set_return_data(&self, data: &[u8]) {
+        self.0.sol_set_return_data(data)
+    }
+
+    fn sol_get_processed_sibling_instruction(&self, index: usize) -> Option<Instruction> {
+        self.0.sol_get_processed_sibling_instruction(index)
+    }
+
+    fn sol_get_stack_height(&self) -> u64 {
+        self.0.sol_get_stack_height()
+    }
+}